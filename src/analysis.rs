@@ -0,0 +1,264 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use rodio::{Decoder, Source};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// Number of samples in each analysis frame
+const FRAME_SIZE: usize = 1024;
+
+/// Number of samples between the start of two consecutive frames
+const HOP_SIZE: usize = 512;
+
+/// Tempo range considered when estimating BPM
+const MIN_BPM: f64 = 60.;
+const MAX_BPM: f64 = 200.;
+
+/// Tempo that BPM estimation leans towards, to avoid picking half or double the real tempo
+const PREFERRED_BPM: f64 = 120.;
+
+/// Decoded song, mixed down to a single channel
+pub struct AudioData {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+/// Decodes an `.ogg` or `.mp3` file from `assets/songs`
+pub fn decode_song(filename: &str) -> AudioData {
+    let file = File::open(format!("assets/songs/{}", filename)).expect("Could not open song file");
+    let decoder = Decoder::new(BufReader::new(file)).expect("Could not decode song file");
+
+    let channels = decoder.channels() as usize;
+    let sample_rate = decoder.sample_rate();
+    let interleaved: Vec<f32> = decoder.convert_samples().collect();
+
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    AudioData {
+        samples,
+        sample_rate,
+    }
+}
+
+/// A point in the song where a new sound starts
+#[derive(Copy, Clone, Debug)]
+pub struct Onset {
+    /// Seconds since the start of the song
+    pub time: f64,
+    /// How pronounced the onset is, from 0 to 1
+    pub strength: f32,
+}
+
+/// Result of analysing a song's audio
+pub struct Analysis {
+    pub onsets: Vec<Onset>,
+    pub bpm: f64,
+    /// Seconds from the start of the song to the first beat
+    pub offset: f64,
+    /// Length of the song in seconds
+    pub duration: f64,
+}
+
+impl Analysis {
+    /// Seconds between two beats
+    pub fn beat_period(&self) -> f64 {
+        60. / self.bpm
+    }
+}
+
+pub fn analyse(audio: &AudioData) -> Analysis {
+    let envelope = onset_envelope(&audio.samples);
+    let frame_duration = HOP_SIZE as f64 / audio.sample_rate as f64;
+
+    let onsets = pick_peaks(&envelope)
+        .into_iter()
+        .map(|frame| Onset {
+            time: frame as f64 * frame_duration,
+            strength: envelope[frame],
+        })
+        .collect();
+
+    let period = beat_period_frames(&envelope, frame_duration);
+    let offset = beat_phase_frames(&envelope, period) * frame_duration;
+
+    Analysis {
+        onsets,
+        bpm: 60. / (period * frame_duration),
+        offset,
+        duration: audio.samples.len() as f64 / audio.sample_rate as f64,
+    }
+}
+
+/// Spectral flux of each frame: how much louder each frequency got since the previous frame.
+/// Peaks in this envelope are where notes start. Normalized so that the largest value is 1.
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    let fft: Arc<dyn Fft<f32>> = FftPlanner::new().plan_fft_forward(FRAME_SIZE);
+    let window: Vec<f32> = (0..FRAME_SIZE)
+        .map(|i| {
+            let phase = 2. * std::f32::consts::PI * i as f32 / FRAME_SIZE as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect();
+
+    let frame_count = samples.len().saturating_sub(FRAME_SIZE) / HOP_SIZE + 1;
+    let mut previous = vec![0.; FRAME_SIZE / 2];
+    let mut envelope = Vec::with_capacity(frame_count);
+    let mut buffer = vec![Complex::new(0., 0.); FRAME_SIZE];
+
+    for frame in 0..frame_count {
+        let start = frame * HOP_SIZE;
+        for (i, value) in buffer.iter_mut().enumerate() {
+            let sample = samples.get(start + i).copied().unwrap_or(0.);
+            *value = Complex::new(sample * window[i], 0.);
+        }
+        fft.process(&mut buffer);
+
+        let mut flux = 0.;
+        for (bin, last) in previous.iter_mut().enumerate() {
+            // log compression keeps quiet passages from disappearing next to loud ones
+            let magnitude = (1. + 10. * buffer[bin].norm()).ln();
+            flux += (magnitude - *last).max(0.);
+            *last = magnitude;
+        }
+        envelope.push(flux);
+    }
+
+    let max = envelope.iter().cloned().fold(0., f32::max);
+    if max > 0. {
+        for value in envelope.iter_mut() {
+            *value /= max;
+        }
+    }
+    envelope
+}
+
+/// Frames that are a local maximum of the envelope and stand out from their surroundings
+fn pick_peaks(envelope: &[f32]) -> Vec<usize> {
+    // frames on either side that a peak must be the maximum of
+    const PEAK_RADIUS: usize = 3;
+    // frames on either side used for the adaptive threshold
+    const MEAN_RADIUS: usize = 16;
+    const THRESHOLD: f32 = 0.05;
+
+    let mut peaks: Vec<usize> = Vec::new();
+    for frame in 0..envelope.len() {
+        let value = envelope[frame];
+
+        let near = frame.saturating_sub(PEAK_RADIUS)..(frame + PEAK_RADIUS + 1).min(envelope.len());
+        if envelope[near].iter().any(|other| *other > value) {
+            continue;
+        }
+
+        let around =
+            frame.saturating_sub(MEAN_RADIUS)..(frame + MEAN_RADIUS + 1).min(envelope.len());
+        let mean = envelope[around.clone()].iter().sum::<f32>() / around.len() as f32;
+        if value < mean + THRESHOLD {
+            continue;
+        }
+
+        // plateaus would otherwise produce one peak per frame
+        if let Some(last) = peaks.last() {
+            if frame - last <= PEAK_RADIUS {
+                continue;
+            }
+        }
+        peaks.push(frame);
+    }
+    peaks
+}
+
+/// Estimates the beat period (in frames, with sub-frame precision) from the autocorrelation of
+/// the onset envelope
+fn beat_period_frames(envelope: &[f32], frame_duration: f64) -> f64 {
+    let mean = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+    let centered: Vec<f32> = envelope.iter().map(|value| value - mean).collect();
+
+    let min_lag = (60. / MAX_BPM / frame_duration).floor() as usize;
+    let max_lag = ((60. / MIN_BPM / frame_duration).ceil() as usize).min(centered.len() / 2);
+    if min_lag >= max_lag {
+        return 60. / PREFERRED_BPM / frame_duration;
+    }
+
+    let autocorrelation = |lag: usize| -> f64 {
+        centered
+            .iter()
+            .zip(centered[lag..].iter())
+            .map(|(a, b)| (*a * *b) as f64)
+            .sum::<f64>()
+    };
+    let correlations: Vec<f64> = (min_lag..=max_lag + 1).map(autocorrelation).collect();
+
+    let weight = |lag: usize| -> f64 {
+        let bpm = 60. / (lag as f64 * frame_duration);
+        let octaves = (bpm / PREFERRED_BPM).log2();
+        (-0.5 * octaves * octaves).exp()
+    };
+    let best = (min_lag..=max_lag)
+        .max_by(|a, b| {
+            let a = correlations[a - min_lag] * weight(*a);
+            let b = correlations[b - min_lag] * weight(*b);
+            a.partial_cmp(&b).unwrap()
+        })
+        .unwrap();
+
+    // parabolic interpolation around the best lag
+    if best == min_lag {
+        return best as f64;
+    }
+    let before = correlations[best - 1 - min_lag];
+    let at = correlations[best - min_lag];
+    let after = correlations[best + 1 - min_lag];
+    let denominator = before - 2. * at + after;
+    if denominator.abs() < f64::EPSILON {
+        best as f64
+    } else {
+        best as f64 + 0.5 * (before - after) / denominator
+    }
+}
+
+/// Finds where the beat grid with the given period lines up best with the onset envelope
+fn beat_phase_frames(envelope: &[f32], period: f64) -> f64 {
+    let phases = period.ceil().max(1.) as usize;
+    let strength = |phase: usize| -> f32 {
+        let mut total = 0.;
+        let mut position = phase as f64;
+        while (position as usize) < envelope.len() {
+            total += envelope[position.round() as usize % envelope.len()];
+            position += period;
+        }
+        total
+    };
+
+    (0..phases)
+        .max_by(|a, b| strength(*a).partial_cmp(&strength(*b)).unwrap())
+        .unwrap_or(0) as f64
+}
+
+/// Fits a beat grid to taps that were made along with the music, one tap per beat.
+/// Returns the BPM and the seconds from the start of the song to the first beat,
+/// or `None` if there are not enough taps yet.
+pub fn tempo_from_taps(taps: &[f64]) -> Option<(f64, f64)> {
+    if taps.len() < 2 {
+        return None;
+    }
+
+    // least squares fit of `tap = offset + beat * period`
+    let n = taps.len() as f64;
+    let mean_beat = (n - 1.) / 2.;
+    let mean_tap = taps.iter().sum::<f64>() / n;
+    let mut covariance = 0.;
+    let mut variance = 0.;
+    for (beat, tap) in taps.iter().enumerate() {
+        covariance += (beat as f64 - mean_beat) * (tap - mean_tap);
+        variance += (beat as f64 - mean_beat).powi(2);
+    }
+    let period = covariance / variance;
+    if period <= 0. {
+        return None;
+    }
+
+    let first_tap = mean_tap - mean_beat * period;
+    Some((60. / period, first_tap.rem_euclid(period)))
+}
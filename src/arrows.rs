@@ -1,6 +1,15 @@
+use crate::audio::ResyncMusic;
+use crate::input::{LaneInputSet, LanePressed};
 use crate::time::ControlledTime;
 use crate::{consts::*, types::SongConfig};
-use crate::{score, types::*};
+use crate::{
+    layout::Layout,
+    modifiers::Modifiers,
+    practice::Practice,
+    score::{self, ArrowJudged, Judgment},
+    settings::Settings,
+    types::*,
+};
 use bevy::prelude::*;
 
 /// Keep textures and materials for arrows
@@ -10,6 +19,16 @@ struct ArrowMaterialResource {
     blue_image: Handle<Image>,
     green_image: Handle<Image>,
     border_image: Handle<Image>,
+    /// Notes of the drum lanes, in the order of `DRUM_LANES`
+    drum_images: Vec<Handle<Image>>,
+}
+
+impl ArrowMaterialResource {
+    fn drum_image(&self, direction: Directions) -> Option<Handle<Image>> {
+        direction
+            .is_drum()
+            .then(|| self.drum_images[direction.lane()].clone())
+    }
 }
 
 // The approach here it to create a handle to the material, so that arrows share a reference vs each having their own copy.
@@ -20,20 +39,83 @@ impl FromWorld for ArrowMaterialResource {
         let blue_handle = asset_server.load("images/arrow_blue.png");
         let green_handle = asset_server.load("images/arrow_green.png");
         let border_handle = asset_server.load("images/arrow_border.png");
+        let drum_images = ["hihat", "snare", "kick", "tom", "crash"]
+            .iter()
+            .map(|piece| asset_server.load(format!("images/drum_{}.png", piece)))
+            .collect();
 
         ArrowMaterialResource {
             red_image: red_handle,
             blue_image: blue_handle,
             green_image: green_handle,
             border_image: border_handle,
+            drum_images,
         }
     }
 }
 
 #[derive(Component)]
-struct Arrow {
-    speed: Speed,
-    direction: Directions,
+pub struct Arrow {
+    pub click_time: f64,
+    pub speed: Speed,
+    /// See `ArrowTime::velocity`
+    pub velocity: f32,
+    pub direction: Directions,
+    /// How far along its path the arrow is, from `SPAWN_POSITION` to `TARGET_POSITION` when it
+    /// should be clicked. The `Layout` places it on the screen.
+    pub position: f32,
+    /// See `ArrowTime::keysound`
+    pub keysound: Option<usize>,
+    /// How far a missed arrow has dropped down the screen
+    fallen: f32,
+}
+
+impl Arrow {
+    /// Distance (in x coordinate val) past the target that this arrow is at the given song time.
+    /// Judging presses by their time instead of the arrow's current position keeps scores
+    /// independent of the frame rate, so replays reproduce them exactly.
+    fn distance_at(&self, time: f64) -> f32 {
+        (time - self.click_time) as f32 * self.speed.value()
+    }
+}
+
+fn spawn_arrow(
+    commands: &mut Commands,
+    materials: &ArrowMaterialResource,
+    layout: &Layout,
+    arrow: &ArrowTime,
+    position: f32,
+) {
+    let texture = materials
+        .drum_image(arrow.direction)
+        .unwrap_or_else(|| match arrow.speed {
+            Speed::Slow => materials.green_image.clone(),
+            Speed::Medium => materials.blue_image.clone(),
+            Speed::Fast => materials.red_image.clone(),
+        });
+
+    let mut transform = Transform::from_translation(layout.translation(arrow.direction, position));
+    transform.rotate(Quat::from_rotation_z(arrow.direction.rotation()));
+
+    commands
+        .spawn(SpriteBundle {
+            texture,
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(140., 140.)),
+                ..Default::default()
+            },
+            transform,
+            ..Default::default()
+        })
+        .insert(Arrow {
+            click_time: arrow.click_time,
+            speed: arrow.speed,
+            velocity: arrow.velocity,
+            direction: arrow.direction,
+            position,
+            keysound: arrow.keysound,
+            fallen: 0.,
+        });
 }
 
 /// Spawn arrows
@@ -41,9 +123,10 @@ fn spawn_arrows(
     mut commands: Commands,
     mut song_config: ResMut<SongConfig>,
     materials: Res<ArrowMaterialResource>,
+    settings: Res<Settings>,
     time: Res<ControlledTime>,
 ) {
-    let secs = time.elapsed_seconds_f64() - 3.;
+    let secs = time.song_seconds_f64();
     let secs_last = secs - time.delta_seconds_f64();
 
     // Count how many arrows got consumed
@@ -51,30 +134,13 @@ fn spawn_arrows(
 
     for arrow in &song_config.arrows {
         if arrow.spawn_time > secs_last && arrow.spawn_time <= secs {
-            let texture = match arrow.speed {
-                Speed::Slow => materials.green_image.clone(),
-                Speed::Medium => materials.blue_image.clone(),
-                Speed::Fast => materials.red_image.clone(),
-            };
-
-            let mut transform =
-                Transform::from_translation(Vec3::new(SPAWN_POSITION, arrow.direction.y(), 1.));
-            transform.rotate(Quat::from_rotation_z(arrow.direction.rotation()));
-
-            commands
-                .spawn(SpriteBundle {
-                    texture,
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(140., 140.)),
-                        ..Default::default()
-                    },
-                    transform,
-                    ..Default::default()
-                })
-                .insert(Arrow {
-                    speed: arrow.speed,
-                    direction: arrow.direction,
-                });
+            spawn_arrow(
+                &mut commands,
+                &materials,
+                &settings.layout,
+                arrow,
+                SPAWN_POSITION,
+            );
             arrows_consumed += 1;
         } else {
             break;
@@ -87,15 +153,100 @@ fn spawn_arrows(
     }
 }
 
+/// Jumps to a position in the song. Arrows whose click time is already past are skipped, and
+/// arrows that would be on their way to the target by then are spawned where they would be.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct SeekSong {
+    /// See `ControlledTime::song_seconds_f64`
+    pub song_time: f64,
+}
+
+/// Handles `SeekSong` events. Systems that send them in the same frame run before this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SeekSet;
+
+/// Chart position a song is started from instead of its beginning, in chart seconds.
+/// Songs started part way through don't count towards high scores or replays.
+#[derive(Resource, Copy, Clone, Debug)]
+pub struct StartPosition(pub f64);
+
+#[allow(clippy::too_many_arguments)]
+fn seek_song(
+    mut commands: Commands,
+    mut seeks: EventReader<SeekSong>,
+    mut song_config: ResMut<SongConfig>,
+    materials: Res<ArrowMaterialResource>,
+    settings: Res<Settings>,
+    mut time: ResMut<ControlledTime>,
+    query: Query<Entity, With<Arrow>>,
+    mut resync: EventWriter<ResyncMusic>,
+) {
+    let Some(seek) = seeks.read().last().copied() else {
+        return;
+    };
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    time.reset_time_at(seek.song_time);
+
+    let mut remaining = Vec::new();
+    for arrow in song_config.arrows.drain(..) {
+        if arrow.click_time < seek.song_time {
+            continue;
+        }
+        if arrow.spawn_time <= seek.song_time {
+            let flown = (seek.song_time - arrow.spawn_time) as f32 * arrow.velocity;
+            spawn_arrow(
+                &mut commands,
+                &materials,
+                &settings.layout,
+                &arrow,
+                SPAWN_POSITION + flown,
+            );
+        } else {
+            remaining.push(arrow);
+        }
+    }
+    song_config.arrows = remaining;
+    resync.send(ResyncMusic);
+}
+
+fn apply_scroll_speed(settings: Res<Settings>, mut song_config: ResMut<SongConfig>) {
+    settings.scroll_speed.apply(&mut song_config.arrows);
+    song_config
+        .arrows
+        .sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
+}
+
+/// Starts the song from its `StartPosition`
+fn start_at_position(
+    start: Res<StartPosition>,
+    modifiers: Res<Modifiers>,
+    mut seeks: EventWriter<SeekSong>,
+) {
+    seeks.send(SeekSong {
+        song_time: start.0 / modifiers.rate as f64,
+    });
+}
+
+fn clear_start_position(mut commands: Commands) {
+    commands.remove_resource::<StartPosition>();
+}
+
 /// Moves arrows forward
-fn move_arrows(time: Res<ControlledTime>, mut query: Query<(&mut Transform, &Arrow)>) {
-    for (mut transform, arrow) in query.iter_mut() {
-        transform.translation.x += time.delta_seconds() * arrow.speed.value();
+fn move_arrows(
+    time: Res<ControlledTime>,
+    settings: Res<Settings>,
+    mut query: Query<(&mut Transform, &mut Arrow)>,
+) {
+    for (mut transform, mut arrow) in query.iter_mut() {
+        arrow.position += time.delta_seconds() * arrow.velocity;
 
         // animate arrow falling after failing
-        let distance_after_target = transform.translation.x - TARGET_POSITION - THRESHOLD;
+        let distance_after_target = arrow.position - TARGET_POSITION - THRESHOLD;
         if distance_after_target > 0.02 {
-            transform.translation.y -= time.delta_seconds() * distance_after_target * 2.;
+            arrow.fallen += time.delta_seconds() * distance_after_target * 2.;
 
             let scale = ((100. - distance_after_target / 3.) / 100.).max(0.2);
             transform.scale = Vec3::splat(scale);
@@ -104,6 +255,9 @@ fn move_arrows(time: Res<ControlledTime>, mut query: Query<(&mut Transform, &Arr
                 -distance_after_target * arrow.speed.multiplier() / 460.,
             ));
         }
+
+        transform.translation = settings.layout.translation(arrow.direction, arrow.position);
+        transform.translation.y -= arrow.fallen;
     }
 }
 
@@ -111,33 +265,78 @@ pub struct ArrowsPlugin;
 impl Plugin for ArrowsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ArrowMaterialResource>()
-            .add_systems(OnEnter(AppState::Game), setup_target_arrows)
+            .add_event::<ArrowJudged>()
+            .add_event::<SeekSong>()
+            .add_event::<ResyncMusic>()
+            .configure_sets(Update, SeekSet.after(JudgmentSet))
+            .add_systems(
+                OnEnter(AppState::Game),
+                (
+                    apply_scroll_speed,
+                    start_at_position.run_if(resource_exists::<StartPosition>()),
+                ),
+            )
+            .add_systems(
+                Update,
+                seek_song.in_set(SeekSet).run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                OnEnter(AppState::Game),
+                (setup_target_arrows, score::reset_score),
+            )
             .add_systems(Update, spawn_arrows.run_if(in_state(AppState::Game)))
             .add_systems(Update, move_arrows.run_if(in_state(AppState::Game)))
-            .add_systems(Update, despawn_arrows.run_if(in_state(AppState::Game)))
-            .add_systems(OnExit(AppState::Game), despawn_target_arrows);
+            .add_systems(
+                Update,
+                (
+                    despawn_arrows.in_set(JudgmentSet),
+                    // practice loops instead of ending
+                    finish_song.run_if(not(resource_exists::<Practice>())),
+                )
+                    .chain()
+                    .after(LaneInputSet)
+                    .run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                OnExit(AppState::Game),
+                (despawn_target_arrows, despawn_remaining_arrows),
+            )
+            // kept through the results, so that they know the song was not played in full
+            .add_systems(OnExit(AppState::Results), clear_start_position);
     }
 }
+
+/// Judges the lane presses of each frame. Systems that react to `ArrowJudged` events in the
+/// same frame run after this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JudgmentSet;
+
 #[derive(Component)]
 struct TargetArrow;
 
 /// Setup target arrows
-fn setup_target_arrows(mut commands: Commands, materials: Res<ArrowMaterialResource>) {
-    for direction in &[
-        Directions::Up,
-        Directions::Down,
-        Directions::Left,
-        Directions::Right,
-    ] {
+fn setup_target_arrows(
+    mut commands: Commands,
+    materials: Res<ArrowMaterialResource>,
+    settings: Res<Settings>,
+    song_config: Res<SongConfig>,
+) {
+    for direction in song_config.lanes.lanes() {
         let mut transform =
-            Transform::from_translation(Vec3::new(TARGET_POSITION, direction.y(), 1.));
+            Transform::from_translation(settings.layout.translation(*direction, TARGET_POSITION));
         transform.rotate(Quat::from_rotation_z(direction.rotation()));
 
+        // drum targets are faded out versions of their notes
+        let (texture, color) = match materials.drum_image(*direction) {
+            Some(image) => (image, Color::rgba(1., 1., 1., 0.35)),
+            None => (materials.border_image.clone(), Color::WHITE),
+        };
         commands
             .spawn(SpriteBundle {
-                texture: materials.border_image.clone(),
+                texture,
                 sprite: Sprite {
                     custom_size: Some(Vec2::new(140., 140.)),
+                    color,
                     ..Default::default()
                 },
                 transform,
@@ -149,26 +348,68 @@ fn setup_target_arrows(mut commands: Commands, materials: Res<ArrowMaterialResou
 
 fn despawn_arrows(
     mut commands: Commands,
-    query: Query<(Entity, &Transform, &Arrow)>,
-    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<ControlledTime>,
+    query: Query<(Entity, &Arrow)>,
+    mut presses: EventReader<LanePressed>,
     mut score: ResMut<score::Score>,
+    mut judged: EventWriter<ArrowJudged>,
 ) {
-    for (entity, transform, arrow) in query.iter() {
-        let pos = transform.translation.x;
-        if pos > TARGET_POSITION - THRESHOLD && pos < TARGET_POSITION + THRESHOLD {
-            // pressed input with correct timing
-            if arrow.direction.key_just_pressed(&keyboard_input) {
-                commands.entity(entity).despawn();
-                score.incr_correct(pos - TARGET_POSITION);
-            }
-        } else if pos > 2. * TARGET_POSITION {
-            // left screen
+    let mut hit: Vec<Entity> = Vec::new();
+
+    for press in presses.read() {
+        // pressed input with correct timing: take the closest arrow in that lane
+        let closest = query
+            .iter()
+            .filter(|(entity, arrow)| arrow.direction == press.direction && !hit.contains(entity))
+            .map(|(entity, arrow)| (entity, arrow.distance_at(press.time), arrow.keysound))
+            .filter(|(_, distance, _)| distance.abs() < THRESHOLD)
+            .min_by(|a, b| a.1.abs().partial_cmp(&b.1.abs()).unwrap());
+
+        if let Some((entity, distance, keysound)) = closest {
+            commands.entity(entity).despawn();
+            score.incr_correct(distance);
+            judged.send(ArrowJudged {
+                direction: press.direction,
+                judgment: Judgment::from_distance(distance),
+                keysound,
+            });
+            hit.push(entity);
+        }
+    }
+
+    let now = time.song_seconds_f64();
+    for (entity, arrow) in query.iter() {
+        // missed by as far as the screen edge is from the target, in chart speed so that the
+        // scroll speed doesn't change when misses count
+        if arrow.distance_at(now) > TARGET_POSITION && !hit.contains(&entity) {
             commands.entity(entity).despawn();
             score.incr_failed();
+            judged.send(ArrowJudged {
+                direction: arrow.direction,
+                judgment: Judgment::Miss,
+                keysound: arrow.keysound,
+            });
         }
     }
 }
 
+/// Shows the results once every arrow of the song has been hit or missed
+fn finish_song(
+    song_config: Res<SongConfig>,
+    query: Query<&Arrow>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if song_config.arrows.is_empty() && query.is_empty() {
+        app_state.set(AppState::Results);
+    }
+}
+
+fn despawn_remaining_arrows(mut commands: Commands, query: Query<Entity, With<Arrow>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 fn despawn_target_arrows(mut commands: Commands, query: Query<(Entity, &TargetArrow)>) {
     for (entity, _) in query.iter() {
         commands.entity(entity).despawn_recursive();
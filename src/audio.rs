@@ -1,7 +1,18 @@
-use bevy::prelude::*;
+use std::{io::Cursor, sync::Arc, time::Duration};
+
+use bevy::{
+    audio::{AddAudioSource, Decodable, Source, Volume},
+    prelude::*,
+    reflect::TypePath,
+};
+use rodio::{source::SkipDuration, Decoder};
 
 use crate::{
     consts::{AppState, START_TIME_OFFSET},
+    modifiers::Modifiers,
+    practice::Practice,
+    settings::{Setting, Settings},
+    stretch::TimeStretch,
     time::ControlledTime,
     types::SongConfig,
 };
@@ -9,35 +20,253 @@ use crate::{
 #[derive(Component)]
 struct MyMusic;
 
-fn setup(mut commands: Commands, song_config: Res<SongConfig>) {
+/// Volume channel a sound plays on, see `Settings::volume`
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub enum SoundChannel {
+    Music,
+    /// Hit sounds and keysounds
+    Hits,
+    /// Menu sounds
+    Ui,
+}
+
+/// Plays a sound once, at its channel's volume
+pub fn play_sound(
+    commands: &mut Commands,
+    settings: &Settings,
+    source: Handle<AudioSource>,
+    channel: SoundChannel,
+) {
     commands.spawn((
         AudioBundle {
-            source: song_config.song_audio.clone(),
+            source,
             settings: PlaybackSettings {
-                paused: true,
-                ..default()
+                volume: Volume::new_relative(settings.volume(channel)),
+                ..PlaybackSettings::DESPAWN
             },
         },
-        MyMusic,
+        channel,
     ));
 }
 
-fn start_song(time: Res<ControlledTime>, music_controller: Query<&AudioSink, With<MyMusic>>) {
+/// A song that starts playing some way into the audio file, for seeking, at the rate of the
+/// modifiers
+#[derive(Asset, TypePath)]
+pub struct SongSection {
+    bytes: Arc<[u8]>,
+    start: Duration,
+    /// Playback speed, which keeps the pitch, see `TimeStretch`
+    rate: f32,
+}
+
+impl Decodable for SongSection {
+    type Decoder = TimeStretch<SkipDuration<Decoder<Cursor<Arc<[u8]>>>>>;
+    type DecoderItem = <Self::Decoder as Iterator>::Item;
+
+    fn decoder(&self) -> Self::Decoder {
+        let decoder = Decoder::new(Cursor::new(self.bytes.clone()))
+            .expect("Could not decode song")
+            .skip_duration(self.start);
+        TimeStretch::new(decoder, self.rate)
+    }
+}
+
+/// Sent after the song clock jumps, so that the music follows it
+#[derive(Event)]
+pub struct ResyncMusic;
+
+/// Set while the music is waiting for its audio file to load before it can seek
+#[derive(Resource, Default)]
+struct PendingResync(bool);
+
+/// The music is set up like after a seek, once its audio file has loaded
+fn setup(mut pending: ResMut<PendingResync>) {
+    pending.0 = true;
+}
+
+fn start_song(
+    time: Res<ControlledTime>,
+    settings: Res<Settings>,
+    music_controller: Query<&AudioSink, With<MyMusic>>,
+) {
     let secs = time.elapsed_seconds();
     let secs_last = secs - time.delta_seconds();
 
-    if secs_last <= START_TIME_OFFSET && START_TIME_OFFSET <= secs {
-        let sink = music_controller
-            .get_single()
-            .expect("failed to get audio player");
-        sink.play();
+    let start = START_TIME_OFFSET - settings.audio_offset as f32;
+    if secs_last <= start && start <= secs {
+        // missing while a seek waits for the audio to load
+        if let Ok(sink) = music_controller.get_single() {
+            sink.play();
+        }
+    }
+}
+
+/// Restarts the music at the song clock's position
+#[allow(clippy::too_many_arguments)]
+fn resync_music(
+    mut commands: Commands,
+    mut resyncs: EventReader<ResyncMusic>,
+    mut pending: ResMut<PendingResync>,
+    time: Res<ControlledTime>,
+    song_config: Res<SongConfig>,
+    modifiers: Res<Modifiers>,
+    practice: Option<Res<Practice>>,
+    settings: Res<Settings>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut sections: ResMut<Assets<SongSection>>,
+    music: Query<Entity, With<MyMusic>>,
+) {
+    if resyncs.read().count() > 0 {
+        pending.0 = true;
+    }
+    if !pending.0 {
+        return;
+    }
+
+    let Some(audio) = audio_sources.get(&song_config.song_audio) else {
+        // try again once the audio file is loaded
+        return;
+    };
+
+    for entity in music.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let rate = practice.map_or(modifiers.rate, |practice| {
+        practice.loop_modifiers(&modifiers).rate
+    });
+    let position = (time.song_seconds_f64() + settings.audio_offset) * rate as f64;
+    let section = sections.add(SongSection {
+        bytes: audio.bytes.clone(),
+        start: Duration::from_secs_f64(position.max(0.)),
+        rate,
+    });
+    commands.spawn((
+        AudioSourceBundle {
+            source: section,
+            settings: PlaybackSettings {
+                // the countdown is not over: play from the start once it is, see `start_song`
+                paused: position < 0.,
+                volume: Volume::new_relative(settings.volume(SoundChannel::Music)),
+                ..default()
+            },
+        },
+        MyMusic,
+        SoundChannel::Music,
+    ));
+    pending.0 = false;
+}
+
+/// Brings the sounds that are playing to their channel's new volume
+fn apply_volumes(settings: Res<Settings>, sinks: Query<(&AudioSink, &SoundChannel)>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (sink, channel) in sinks.iter() {
+        sink.set_volume(settings.volume(*channel));
+    }
+}
+
+/// F4 mutes everything, F5 and F6 turn the music down and up, F7 and F8 the hit sounds.
+/// These work anywhere, even during a song.
+fn volume_keys(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut popup: Query<(&mut Text, &mut VolumePopup)>,
+) {
+    let changes = [
+        (KeyCode::F5, SoundChannel::Music, -1),
+        (KeyCode::F6, SoundChannel::Music, 1),
+        (KeyCode::F7, SoundChannel::Hits, -1),
+        (KeyCode::F8, SoundChannel::Hits, 1),
+    ];
+    let mut message = None;
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        settings.muted = !settings.muted;
+        message = Some(if settings.muted { "Muted" } else { "Unmuted" }.to_string());
+    }
+    for (key, channel, steps) in changes {
+        if keyboard_input.just_pressed(key) {
+            settings.change(Setting::Volume(channel), steps);
+            let name = match channel {
+                SoundChannel::Music => "Music",
+                SoundChannel::Hits => "Hit sounds",
+                SoundChannel::Ui => "Menu sounds",
+            };
+            message = Some(format!(
+                "{}: {}",
+                name,
+                settings.describe(Setting::Volume(channel))
+            ));
+        }
+    }
+
+    if let Some(message) = message {
+        settings.save();
+        for (mut text, mut popup) in popup.iter_mut() {
+            text.sections[0].value = message.clone();
+            popup.0.reset();
+        }
+    }
+}
+
+/// Shows the volume for a moment after it's changed with the volume keys
+#[derive(Component)]
+struct VolumePopup(Timer);
+
+fn setup_volume_popup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.),
+            bottom: Val::Px(10.),
+            ..default()
+        }),
+        VolumePopup(Timer::from_seconds(1.5, TimerMode::Once)),
+    ));
+}
+
+fn hide_volume_popup(time: Res<Time>, mut popup: Query<(&mut Text, &mut VolumePopup)>) {
+    for (mut text, mut popup) in popup.iter_mut() {
+        if popup.0.tick(time.delta()).just_finished() {
+            text.sections[0].value.clear();
+        }
+    }
+}
+
+fn despawn_music(
+    mut commands: Commands,
+    query: Query<Entity, With<MyMusic>>,
+    mut pending: ResMut<PendingResync>,
+) {
+    pending.0 = false;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 }
 
 pub struct AudioPlugin;
 impl Plugin for AudioPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::Game), setup)
-            .add_systems(Update, start_song.run_if(in_state(AppState::Game)));
+        app.add_audio_source::<SongSection>()
+            .add_event::<ResyncMusic>()
+            .init_resource::<PendingResync>()
+            .add_systems(Startup, setup_volume_popup)
+            .add_systems(
+                Update,
+                (volume_keys, apply_volumes, hide_volume_popup).chain(),
+            )
+            .add_systems(OnEnter(AppState::Game), setup)
+            .add_systems(Update, start_song.run_if(in_state(AppState::Game)))
+            // after `Update`, so that the music follows clock jumps in the same frame
+            .add_systems(PostUpdate, resync_music.run_if(in_state(AppState::Game)))
+            .add_systems(OnExit(AppState::Game), despawn_music);
     }
 }
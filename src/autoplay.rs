@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+use crate::{
+    arrows::Arrow,
+    consts::AppState,
+    input::{LaneInputSet, LanePressed},
+    time::ControlledTime,
+};
+
+/// When enabled, a bot presses every arrow exactly on time instead of the keyboard
+#[derive(Resource, Default)]
+pub struct Autoplay {
+    pub enabled: bool,
+    /// Attract mode from the menu: any key goes back to the menu
+    pub demo: bool,
+    /// Whether autoplay played any part of the current song. Such runs are not saved.
+    pub used: bool,
+}
+
+pub fn autoplay_enabled(autoplay: Res<Autoplay>) -> bool {
+    autoplay.enabled
+}
+
+/// Marks arrows that the bot already pressed
+#[derive(Component)]
+struct AutoPressed;
+
+/// Presses each arrow's lane at the arrow's click time. Like a player, the bot presses a lane at
+/// most once per instant, so arrows stacked on top of each other in one lane are not all hit.
+fn autoplay_lane_presses(
+    mut commands: Commands,
+    time: Res<ControlledTime>,
+    mut autoplay: ResMut<Autoplay>,
+    query: Query<(Entity, &Arrow), Without<AutoPressed>>,
+    mut presses: EventWriter<LanePressed>,
+) {
+    autoplay.used = true;
+    let now = time.song_seconds_f64();
+
+    let mut due: Vec<LanePressed> = Vec::new();
+    for (entity, arrow) in query.iter() {
+        if arrow.click_time > now {
+            continue;
+        }
+        commands.entity(entity).insert(AutoPressed);
+
+        let press = LanePressed {
+            direction: arrow.direction,
+            time: arrow.click_time,
+            velocity: None,
+        };
+        let already_pressed = due
+            .iter()
+            .any(|other| other.direction == press.direction && other.time == press.time);
+        if !already_pressed {
+            due.push(press);
+        }
+    }
+
+    due.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    presses.send_batch(due);
+}
+
+/// F2 turns autoplay on and off during a song
+fn toggle_autoplay(keyboard_input: Res<Input<KeyCode>>, mut autoplay: ResMut<Autoplay>) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        autoplay.enabled = !autoplay.enabled;
+    }
+}
+
+fn end_demo_on_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.get_just_pressed().next().is_some() {
+        app_state.set(AppState::Menu);
+    }
+}
+
+fn start_song(mut autoplay: ResMut<Autoplay>) {
+    autoplay.used = autoplay.enabled;
+}
+
+/// Demos go straight back to the menu instead of showing their score
+fn skip_results(mut app_state: ResMut<NextState<AppState>>) {
+    app_state.set(AppState::Menu);
+}
+
+/// Autoplay only lasts for one song
+fn reset_autoplay(mut autoplay: ResMut<Autoplay>) {
+    *autoplay = Autoplay::default();
+}
+
+pub struct AutoplayPlugin;
+impl Plugin for AutoplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Autoplay>()
+            .add_systems(OnEnter(AppState::Game), start_song)
+            .add_systems(
+                Update,
+                autoplay_lane_presses
+                    .in_set(LaneInputSet)
+                    .run_if(in_state(AppState::Game))
+                    .run_if(autoplay_enabled),
+            )
+            .add_systems(
+                Update,
+                (
+                    toggle_autoplay.run_if(|autoplay: Res<Autoplay>| !autoplay.demo),
+                    end_demo_on_input.run_if(|autoplay: Res<Autoplay>| autoplay.demo),
+                )
+                    .run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                OnEnter(AppState::Results),
+                skip_results.run_if(|autoplay: Res<Autoplay>| autoplay.demo),
+            )
+            // kept until the menu, so that the results know whether autoplay was used
+            .add_systems(OnEnter(AppState::Menu), reset_autoplay);
+    }
+}
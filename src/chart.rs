@@ -0,0 +1,266 @@
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    autoplay::Autoplay,
+    consts::AppState,
+    modifiers::Modifiers,
+    practice::Practice,
+    replay::{stop_playback, ReplayPlayback},
+    settings::Settings,
+    storage,
+    time::ControlledTime,
+    types::{chart_hash, parse_config, SongConfig, SongConfigToml},
+};
+
+/// A chart file from `assets/songs`, loaded through the `AssetServer`
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct Chart {
+    /// The chart, upgraded to the latest format version
+    pub config: SongConfigToml,
+    /// See `types::chart_hash`
+    pub hash: String,
+}
+
+/// File that the song index is kept in, see `SongIndex`
+const SONG_INDEX_FILE: &str = "assets/songs.index.toml";
+
+/// Where the song index is among the assets
+pub const SONG_INDEX: &str = "songs.index.toml";
+
+const SONG_INDEX_HEADER: &str =
+    "# Charts in assets/songs that the menu lists. `drum-city index` rebuilds this.\n";
+
+/// The charts in `assets/songs` that the menu lists. Browsers can't list a folder, so the game
+/// reads this index instead, and the tools that write new charts add them to it.
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SongIndex {
+    /// Chart files in `assets/songs`, in menu order
+    pub charts: Vec<String>,
+}
+
+impl SongIndex {
+    pub fn read() -> SongIndex {
+        storage::read(SONG_INDEX_FILE)
+            .map(|contents| toml::from_str(&contents).expect("Could not parse song index"))
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self) {
+        let text = toml::to_string(self).expect("Couldn't convert song index to toml text");
+        storage::write(SONG_INDEX_FILE, &format!("{}{}", SONG_INDEX_HEADER, text));
+    }
+
+    /// Lists a chart in the index file, if it isn't already
+    pub fn add_chart(chart: &str) {
+        let mut index = SongIndex::read();
+        if !index.charts.iter().any(|listed| listed == chart) {
+            index.charts.push(chart.to_string());
+            index.write();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TomlAssetError {
+    Io(std::io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for TomlAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TomlAssetError::Io(error) => write!(f, "Could not read file: {}", error),
+            TomlAssetError::Parse(error) => write!(f, "Could not parse file: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TomlAssetError {}
+
+async fn read_text(reader: &mut Reader<'_>) -> Result<String, TomlAssetError> {
+    let mut contents = String::new();
+    reader
+        .read_to_string(&mut contents)
+        .await
+        .map_err(TomlAssetError::Io)?;
+    Ok(contents)
+}
+
+/// Loads `.toml` files as charts. Besides the song index, charts are the only TOML files among
+/// the assets.
+#[derive(Default)]
+pub struct ChartLoader;
+
+impl AssetLoader for ChartLoader {
+    type Asset = Chart;
+    type Settings = ();
+    type Error = TomlAssetError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Chart, TomlAssetError>> {
+        Box::pin(async move {
+            let contents = read_text(reader).await?;
+            let config = parse_config(&contents).map_err(TomlAssetError::Parse)?;
+            Ok(Chart {
+                config,
+                hash: chart_hash(&contents),
+            })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["toml"]
+    }
+}
+
+/// Loads the song index. Its longer extension wins over the chart loader's.
+#[derive(Default)]
+pub struct SongIndexLoader;
+
+impl AssetLoader for SongIndexLoader {
+    type Asset = SongIndex;
+    type Settings = ();
+    type Error = TomlAssetError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<SongIndex, TomlAssetError>> {
+        Box::pin(async move {
+            let contents = read_text(reader).await?;
+            toml::from_str(&contents).map_err(|error| TomlAssetError::Parse(error.to_string()))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["index.toml"]
+    }
+}
+
+/// A song that starts as soon as its chart has loaded. Menus insert this rather than a
+/// `SongConfig`, and set up anything else the song needs, like `Autoplay`, beforehand.
+#[derive(Resource)]
+pub struct PendingSong {
+    /// Chart file in `assets/songs`
+    pub path: String,
+    pub chart: Handle<Chart>,
+    /// Practices the song instead of playing it
+    pub practice: bool,
+}
+
+impl PendingSong {
+    pub fn new(path: &str, asset_server: &AssetServer) -> Self {
+        Self {
+            path: path.to_string(),
+            chart: asset_server.load(format!("songs/{}", path)),
+            practice: false,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_pending_song(
+    mut commands: Commands,
+    pending: Res<PendingSong>,
+    charts: Res<Assets<Chart>>,
+    asset_server: Res<AssetServer>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut autoplay: ResMut<Autoplay>,
+    playback: Option<Res<ReplayPlayback>>,
+    modifiers: ResMut<Modifiers>,
+) {
+    let Some(chart) = charts.get(&pending.chart) else {
+        if asset_server.get_load_state(&pending.chart) == Some(LoadState::Failed) {
+            error!("Could not load chart {}", pending.path);
+            commands.remove_resource::<PendingSong>();
+            // back to the menu as it was, without the replay or autoplay the song was set up with
+            *autoplay = Autoplay::default();
+            stop_playback(commands, playback, modifiers);
+        }
+        return;
+    };
+
+    let config = SongConfig::from_chart(&pending.path, pending.chart.clone(), chart, &asset_server);
+    if pending.practice {
+        commands.insert_resource(Practice::new(&config));
+    }
+    commands.insert_resource(config);
+    commands.remove_resource::<PendingSong>();
+    app_state.set(AppState::Game);
+}
+
+/// Swaps in the new version of the chart being played when its file changes. The song carries
+/// on from where it is: arrows already on their way stay as they are, judged or not, and the
+/// ones still to spawn come from the new version.
+#[allow(clippy::too_many_arguments)]
+fn reload_chart(
+    mut events: EventReader<AssetEvent<Chart>>,
+    charts: Res<Assets<Chart>>,
+    asset_server: Res<AssetServer>,
+    time: Res<ControlledTime>,
+    modifiers: Res<Modifiers>,
+    settings: Res<Settings>,
+    practice: Option<ResMut<Practice>>,
+    mut song_config: ResMut<SongConfig>,
+) {
+    if !events
+        .read()
+        .any(|event| event.is_modified(&song_config.chart))
+    {
+        return;
+    }
+    let Some(chart) = charts.get(&song_config.chart) else {
+        return;
+    };
+
+    let mut reloaded = SongConfig::from_chart(
+        &song_config.path,
+        song_config.chart.clone(),
+        chart,
+        &asset_server,
+    );
+    reloaded.arrows = match practice {
+        Some(mut practice) => practice.reload(&reloaded.arrows, &modifiers, &settings.scroll_speed),
+        None => {
+            let mut arrows = reloaded.arrows;
+            modifiers.apply(&mut arrows);
+            settings.scroll_speed.apply(&mut arrows);
+            arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
+            arrows
+        }
+    };
+    // this frame's arrows have spawned already, see `ChartPlugin`
+    let now = time.song_seconds_f64();
+    reloaded.arrows.retain(|arrow| arrow.spawn_time > now);
+    info!("Reloaded chart {}", reloaded.path);
+    *song_config = reloaded;
+}
+
+pub struct ChartPlugin;
+impl Plugin for ChartPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Chart>()
+            .init_asset::<SongIndex>()
+            .init_asset_loader::<ChartLoader>()
+            .init_asset_loader::<SongIndexLoader>()
+            .add_systems(
+                Update,
+                start_pending_song.run_if(resource_exists::<PendingSong>()),
+            )
+            // after every arrow due this frame has spawned
+            .add_systems(PostUpdate, reload_chart.run_if(in_state(AppState::Game)));
+    }
+}
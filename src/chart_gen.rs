@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    analysis::{analyse, decode_song, Analysis},
+    migrations::FORMAT_VERSION,
+    types::*,
+};
+
+/// Arrows are never generated before this song time, so that there is time to read the first one
+const MIN_CLICK_TIME: f64 = 1.;
+
+pub struct GeneratorOptions {
+    pub difficulty: Difficulty,
+    /// Fraction of the candidate beats that get an arrow, from 0 to 1. Each difficulty has a default.
+    pub density: Option<f64>,
+    pub seed: u64,
+}
+
+/// Small deterministic random number generator (xorshift64*), so that the same seed always
+/// produces the same chart
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Number of arrow slots per beat
+fn subdivisions(difficulty: Difficulty) -> u32 {
+    match difficulty {
+        Difficulty::Easy => 1,
+        Difficulty::Medium => 2,
+        Difficulty::Hard => 4,
+    }
+}
+
+fn default_density(difficulty: Difficulty) -> f64 {
+    match difficulty {
+        Difficulty::Easy => 0.5,
+        Difficulty::Medium => 0.6,
+        Difficulty::Hard => 0.7,
+    }
+}
+
+/// Decodes and analyses a song from `assets/songs`, and generates a chart for it
+pub fn generate_chart(filename: &str, name: &str, options: &GeneratorOptions) -> SongConfigToml {
+    let audio = decode_song(filename);
+    let analysis = analyse(&audio);
+
+    SongConfigToml {
+        format_version: FORMAT_VERSION,
+        name: name.to_string(),
+        filename: filename.to_string(),
+        bpm: Some(analysis.bpm),
+        offset: Some(analysis.offset),
+        difficulty: Some(options.difficulty),
+        lanes: LaneSet::Arrows,
+        arrows: generate_arrows(&analysis, options),
+    }
+}
+
+/// Places arrows on the beat grid where the song has its strongest onsets
+pub fn generate_arrows(analysis: &Analysis, options: &GeneratorOptions) -> Vec<ArrowTimeToml> {
+    let step = analysis.beat_period() / subdivisions(options.difficulty) as f64;
+
+    // strongest onset that snaps to each slot of the grid
+    let mut slots: BTreeMap<i64, f32> = BTreeMap::new();
+    for onset in &analysis.onsets {
+        let slot = ((onset.time - analysis.offset) / step).round() as i64;
+        if analysis.offset + slot as f64 * step < MIN_CLICK_TIME {
+            continue;
+        }
+        let strength = slots.entry(slot).or_insert(0.);
+        *strength = strength.max(onset.strength);
+    }
+
+    let density = options
+        .density
+        .unwrap_or_else(|| default_density(options.difficulty))
+        .clamp(0., 1.);
+    let mut chosen: Vec<(i64, f32)> = slots.into_iter().collect();
+    chosen.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    chosen.truncate((chosen.len() as f64 * density).round() as usize);
+    chosen.sort_by_key(|(slot, _)| *slot);
+
+    let mut strengths: Vec<f32> = chosen.iter().map(|(_, strength)| *strength).collect();
+    strengths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let quantile = |q: f64| -> f32 {
+        strengths
+            .get(((strengths.len() as f64 - 1.) * q).round() as usize)
+            .copied()
+            .unwrap_or(1.)
+    };
+    let (strong, very_strong) = (quantile(0.75), quantile(0.9));
+
+    let mut rng = Rng::new(options.seed);
+    let mut history: Vec<Directions> = Vec::new();
+    let mut last_time = f64::NEG_INFINITY;
+    let mut arrows = Vec::with_capacity(chosen.len());
+
+    for (slot, strength) in chosen {
+        let click_time = analysis.offset + slot as f64 * step;
+        let is_quick = click_time - last_time < analysis.beat_period();
+        let direction = pick_direction(&mut rng, &history, is_quick);
+
+        let speed = match options.difficulty {
+            Difficulty::Easy => Speed::Slow,
+            Difficulty::Medium if strength >= strong => Speed::Medium,
+            Difficulty::Hard if strength >= very_strong => Speed::Fast,
+            Difficulty::Hard if strength >= strong => Speed::Medium,
+            _ => Speed::Slow,
+        };
+
+        arrows.push(ArrowTimeToml {
+            click_time,
+            speed,
+            direction,
+            keysound: None,
+        });
+        history.push(direction);
+        last_time = click_time;
+    }
+    arrows
+}
+
+/// Picks a direction for the next arrow that avoids awkward patterns:
+/// - the same direction twice in a row when the arrows are close together
+/// - the same direction three times in a row
+/// - ping-ponging between two directions (Up, Left, Up, Left)
+fn pick_direction(rng: &mut Rng, history: &[Directions], is_quick: bool) -> Directions {
+    let mut excluded: Vec<Directions> = Vec::new();
+    match history {
+        [.., before, last] if is_quick || before == last => excluded.push(*last),
+        [last] if is_quick => excluded.push(*last),
+        _ => {}
+    }
+    if let [.., third, second, last] = history {
+        if third == last {
+            excluded.push(*second);
+        }
+    }
+
+    let candidates: Vec<Directions> = [
+        Directions::Up,
+        Directions::Down,
+        Directions::Left,
+        Directions::Right,
+    ]
+    .into_iter()
+    .filter(|direction| !excluded.contains(direction))
+    .collect();
+
+    candidates[rng.below(candidates.len())]
+}
@@ -0,0 +1,432 @@
+use std::{
+    fs::{copy, create_dir_all, read, read_dir, read_to_string, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use bevy::prelude::Resource;
+
+use crate::{
+    analysis::{analyse, decode_song},
+    chart::SongIndex,
+    chart_gen::{generate_chart, GeneratorOptions},
+    export::ExportFormat,
+    headless::Simulation,
+    high_scores::HighScores,
+    midi_import::{import_chart, ImportOptions, DRUM_CHANNEL},
+    migrations::{format_version, FORMAT_VERSION},
+    replay::Replay,
+    types::*,
+};
+
+const USAGE: &str = "Usage:
+  drum-city                      start the game
+  drum-city play <chart> [--start SECONDS]
+                                 start the game playing a chart, optionally part way through
+  drum-city generate <song> <chart> [--difficulty easy|medium|hard] [--density 0-1] [--name NAME] [--seed N]
+                                 generate a chart for an audio file, both in assets/songs
+  drum-city import <midi> <song> <chart> [--channel 1-16] [--track N] [--offset SECONDS] [--name NAME] [--difficulty easy|medium|hard]
+                                 make a drum chart for a song in assets/songs from the drum
+                                 track of a MIDI file (channel 10 unless told otherwise)
+  drum-city export <folder> <output folder> [--format sm|osu]
+                                 convert every chart of a folder to StepMania and osu!mania
+                                 charts, copying their songs along
+  drum-city index                 list every chart in assets/songs in the song index, which the
+                                 menu shows
+  drum-city migrate [chart]...    rewrite charts in assets/songs to the latest format version,
+                                 every chart there if none are given
+  drum-city bpm <chart>          estimate the BPM and first beat offset of a chart's song and
+                                 write them into the chart's header
+  drum-city verify <chart>...    let autoplay play each chart and check that every arrow can
+                                 be hit";
+
+/// Runs the command line tools for chart authors. Returns `false` if no command was given, in
+/// which case the game should start.
+pub fn run() -> bool {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("play") => return false,
+        Some("generate") => generate(&args[1..]),
+        Some("import") => import(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("index") => index(),
+        Some("migrate") => migrate(&args[1..]),
+        Some("bpm") => bpm(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        Some(_) => fail("Unknown command"),
+    }
+    true
+}
+
+/// Song to play as soon as the game starts, from the `play` command
+#[derive(Resource, Debug)]
+pub struct LaunchSong {
+    pub chart: String,
+    /// Chart position to start from, in seconds
+    pub start: Option<f64>,
+}
+
+pub fn launch_song() -> Option<LaunchSong> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("play") {
+        return None;
+    }
+    let [chart] = positional(&args[1..])[..] else {
+        fail("play needs a chart file");
+    };
+    Some(LaunchSong {
+        chart: chart.to_string(),
+        start: flag(&args[1..], "--start").map(|value| {
+            value
+                .parse()
+                .ok()
+                .filter(|start: &f64| *start >= 0.)
+                .unwrap_or_else(|| fail("--start must be a number of seconds, 0 or more"))
+        }),
+    })
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    exit(1);
+}
+
+/// Value following `--name` in the arguments
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .map(|index| match args.get(index + 1) {
+            Some(value) => value.as_str(),
+            None => fail(&format!("Missing value for {}", name)),
+        })
+}
+
+/// Arguments that are not flags or flag values
+fn positional(args: &[String]) -> Vec<&str> {
+    let mut positional = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+        } else if arg.starts_with("--") {
+            skip_value = true;
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+    positional
+}
+
+pub fn parse_difficulty(value: &str) -> Difficulty {
+    match value.to_lowercase().as_str() {
+        "easy" => Difficulty::Easy,
+        "medium" => Difficulty::Medium,
+        "hard" => Difficulty::Hard,
+        _ => fail(&format!("Unknown difficulty: {}", value)),
+    }
+}
+
+/// Writes a chart to `assets/songs`, and lists it in the song index if it's new
+pub fn write_config(path: &str, config: &SongConfigToml) {
+    let text = toml::to_string(config).expect("Couldn't convert chart to toml text");
+    let mut file =
+        File::create(format!("assets/songs/{}", path)).expect("Couldn't open chart file");
+    file.write_all(text.as_bytes())
+        .expect("Couldn't write to chart file");
+    SongIndex::add_chart(path);
+}
+
+/// Chart files in `assets/songs`, by file name
+fn song_files() -> Vec<String> {
+    let mut charts: Vec<String> = read_dir("assets/songs")
+        .expect("Could not read assets/songs")
+        .map(|entry| entry.expect("Could not read assets/songs").path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "toml")
+        })
+        .filter_map(|path| path.file_name()?.to_str().map(String::from))
+        .collect();
+    charts.sort();
+    charts
+}
+
+fn generate(args: &[String]) {
+    let [song, chart] = positional(args)[..] else {
+        fail("generate needs a song and a chart file");
+    };
+
+    let difficulty = flag(args, "--difficulty").map_or(Difficulty::Medium, parse_difficulty);
+    let options = GeneratorOptions {
+        difficulty,
+        density: flag(args, "--density").map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| fail("--density must be a number"))
+        }),
+        seed: flag(args, "--seed").map_or(1, |value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| fail("--seed must be a number"))
+        }),
+    };
+    let name = flag(args, "--name")
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{} ({:?})", song, difficulty));
+
+    let config = generate_chart(song, &name, &options);
+    write_config(chart, &config);
+
+    // make sure the game can read what we wrote
+    let written = read_config(chart);
+    println!(
+        "Wrote {} arrows at {:.1} BPM to assets/songs/{}",
+        written.arrows.len(),
+        written.bpm.unwrap_or_default(),
+        chart
+    );
+}
+
+fn import(args: &[String]) {
+    let [midi, song, chart] = positional(args)[..] else {
+        fail("import needs a MIDI file, a song and a chart file");
+    };
+
+    let number = |name: &str| {
+        flag(args, name).map(|value| {
+            value
+                .parse::<usize>()
+                .ok()
+                .filter(|number| *number >= 1)
+                .unwrap_or_else(|| fail(&format!("{} must be a number from 1", name)))
+        })
+    };
+    let channel = number("--channel").map_or(DRUM_CHANNEL, |channel| {
+        if channel > 16 {
+            fail("--channel must be from 1 to 16");
+        }
+        channel as u8 - 1
+    });
+    let options = ImportOptions {
+        track: number("--track").map(|track| track - 1),
+        channel,
+        offset: flag(args, "--offset").map_or(0., |value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| fail("--offset must be a number of seconds"))
+        }),
+        difficulty: flag(args, "--difficulty").map(parse_difficulty),
+    };
+    let name = flag(args, "--name")
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{} (drums)", song));
+
+    let bytes =
+        read(midi).unwrap_or_else(|error| fail(&format!("Could not read {}: {}", midi, error)));
+    let config = import_chart(&bytes, song, &name, &options).unwrap_or_else(|error| fail(&error));
+    write_config(chart, &config);
+
+    // make sure the game can read what we wrote
+    let written = read_config(chart);
+    println!(
+        "Wrote {} drum notes to assets/songs/{}",
+        written.arrows.len(),
+        chart
+    );
+}
+
+fn export(args: &[String]) {
+    let [folder, output] = positional(args)[..] else {
+        fail("export needs a folder of charts and an output folder");
+    };
+    let formats = match flag(args, "--format") {
+        None => vec![ExportFormat::Sm, ExportFormat::Osu],
+        Some("sm") => vec![ExportFormat::Sm],
+        Some("osu") => vec![ExportFormat::Osu],
+        Some(format) => fail(&format!("Unknown format: {}", format)),
+    };
+
+    let mut charts: Vec<PathBuf> = read_dir(folder)
+        .unwrap_or_else(|error| fail(&format!("Could not read {}: {}", folder, error)))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "toml")
+        })
+        .collect();
+    charts.sort();
+    create_dir_all(output).expect("Couldn't create output folder");
+
+    for chart in charts {
+        let text = read_to_string(&chart).expect("Could not read chart file");
+        let config = match parse_config(&text) {
+            Ok(config) => config,
+            Err(error) => {
+                println!("{}: skipped, not a chart: {}", chart.display(), error);
+                continue;
+            }
+        };
+        let exported: Result<Vec<String>, String> = formats
+            .iter()
+            .map(|format| format.export(&config))
+            .collect();
+        let exported = match exported {
+            Ok(exported) => exported,
+            Err(error) => {
+                println!("{}: skipped, {}", chart.display(), error);
+                continue;
+            }
+        };
+
+        let stem = chart.file_stem().expect("Chart files have a name");
+        for (format, text) in formats.iter().zip(exported) {
+            let path = Path::new(output)
+                .join(stem)
+                .with_extension(format.extension());
+            let mut file = File::create(&path).expect("Couldn't open exported chart file");
+            file.write_all(text.as_bytes())
+                .expect("Couldn't write to exported chart file");
+        }
+
+        // the charts point at their song, which has to be next to them
+        let song = Path::new(output).join(&config.filename);
+        if !song.exists() {
+            if let Err(error) = copy(Path::new(folder).join(&config.filename), &song) {
+                println!(
+                    "{}: could not copy {}: {}",
+                    chart.display(),
+                    config.filename,
+                    error
+                );
+            }
+        }
+        println!("{}: exported", chart.display());
+    }
+}
+
+fn index() {
+    let mut charts = Vec::new();
+    for chart in song_files() {
+        match parse_config(&read_chart_file(&chart)) {
+            Ok(_) => charts.push(chart),
+            Err(error) => println!("{}: skipped, not a chart: {}", chart, error),
+        }
+    }
+    println!("{} charts in the song index", charts.len());
+    SongIndex { charts }.write();
+}
+
+/// Carries a chart's high scores and replay over to its new hash after `migrate` rewrote it, as
+/// it still plays the same
+fn keep_scores(chart: &str, old_hash: &str, new_hash: &str) {
+    let mut high_scores = HighScores::load();
+    if high_scores.rehash(old_hash, new_hash) {
+        high_scores.save();
+    }
+    if Replay::exists(chart) {
+        match Replay::load(chart) {
+            Ok(mut replay) if replay.chart_hash == old_hash => {
+                replay.chart_hash = new_hash.to_string();
+                replay.save();
+            }
+            Ok(_) => {}
+            Err(error) => println!("{}: replay not carried over, {}", chart, error),
+        }
+    }
+}
+
+fn migrate(args: &[String]) {
+    let mut charts: Vec<String> = positional(args).into_iter().map(String::from).collect();
+    if charts.is_empty() {
+        charts = song_files();
+    }
+
+    for chart in charts {
+        let text = read_chart_file(&chart);
+        let version = toml::from_str(&text)
+            .map_err(|error: toml::de::Error| error.to_string())
+            .and_then(|chart| format_version(&chart));
+        let version = match version {
+            Ok(FORMAT_VERSION) => {
+                println!("{}: already at version {}", chart, FORMAT_VERSION);
+                continue;
+            }
+            Ok(version) => version,
+            Err(error) => {
+                println!("{}: skipped, not a chart: {}", chart, error);
+                continue;
+            }
+        };
+        // rewriting the whole chart drops its comments, so charts already up to date are left be
+        match parse_config(&text) {
+            Ok(config) => {
+                write_config(&chart, &config);
+                keep_scores(
+                    &chart,
+                    &chart_hash(&text),
+                    &chart_hash(&read_chart_file(&chart)),
+                );
+                println!("{}: version {} -> {}", chart, version, FORMAT_VERSION);
+            }
+            Err(error) => println!("{}: skipped, {}", chart, error),
+        }
+    }
+}
+
+fn bpm(args: &[String]) {
+    let [chart] = positional(args)[..] else {
+        fail("bpm needs a chart file");
+    };
+
+    let mut config = read_config(chart);
+    let analysis = analyse(&decode_song(&config.filename));
+    config.bpm = Some(analysis.bpm);
+    config.offset = Some(analysis.offset);
+    write_config(chart, &config);
+
+    println!(
+        "{}: {:.1} BPM, first beat at {:.3}s",
+        chart, analysis.bpm, analysis.offset
+    );
+}
+
+fn verify(args: &[String]) {
+    let charts = positional(args);
+    if charts.is_empty() {
+        fail("verify needs at least one chart file");
+    }
+
+    let mut all_clear = true;
+    for chart in charts {
+        let config = read_config(chart);
+        let arrows = config.arrows.len();
+        let last_click = config
+            .arrows
+            .iter()
+            .map(|arrow| arrow.click_time)
+            .fold(0., f64::max);
+
+        let mut simulation = Simulation::new(chart);
+        simulation.enable_autoplay();
+        simulation.run_until(last_click + 5.);
+
+        let score = simulation.score();
+        let clear = score.get_corrects() == arrows && score.get_fails() == 0;
+        all_clear &= clear;
+        println!(
+            "{}: {} {}/{} arrows hit, {} misses, score {}/{}",
+            chart,
+            if clear { "OK" } else { "FAIL" },
+            score.get_corrects(),
+            arrows,
+            score.get_fails(),
+            score.get_score(),
+            100 * arrows
+        );
+    }
+
+    if !all_clear {
+        exit(1);
+    }
+}
@@ -3,15 +3,21 @@ use bevy::prelude::*;
 // Speed at which a Slow arrow moves
 pub const BASE_SPEED: f32 = 200.;
 
-/// X coordinate value where arrows spaw. should be off the screen.
+/// Position along their path where arrows spawn. should be off the screen.
 pub const SPAWN_POSITION: f32 = -400.;
 
-/// X coordinate value where arrows should be clicked
+/// Position along their path where arrows should be clicked. `Layout` places it on the screen.
 pub const TARGET_POSITION: f32 = 200.;
 
 /// X coordinate value where arrows appear in Map Maker mode
 pub const MAP_MAKER_POSITION: f32 = 0.;
 
+/// Seconds between two presses in the same lane for a Map Maker overdub to treat them as the same arrow
+pub const OVERDUB_CONFLICT_WINDOW: f64 = 0.1;
+
+/// Seconds without a tap after which tap tempo starts over
+pub const TAP_TEMPO_RESET: f64 = 2.;
+
 /// Margin of error (in x coordinate val) for clicking on an arrow
 pub const THRESHOLD: f32 = 20.;
 
@@ -21,6 +27,21 @@ pub const DISTANCE: f32 = TARGET_POSITION - SPAWN_POSITION;
 /// Number of seconds to wait before audio plays
 pub const START_TIME_OFFSET: f32 = 3.;
 
+/// Seconds of inactivity on the menu before an autoplay demo starts
+pub const ATTRACT_MODE_DELAY: f32 = 20.;
+
+/// Directory where the latest replay of each chart is saved
+pub const REPLAYS_DIR: &str = "replays";
+
+/// File where the best scores of each chart are saved
+pub const SCORES_FILE: &str = "scores.toml";
+
+/// File where the player's settings are saved
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// Number of scores kept for each chart
+pub const HIGH_SCORES_PER_CHART: usize = 10;
+
 /// Width of the window
 pub const WINDOW_WIDTH: f32 = 800.;
 
@@ -36,5 +57,9 @@ pub enum AppState {
     #[default]
     Menu,
     Game,
+    /// Score of the song that was just played
+    Results,
     MakeMap,
+    /// Editing the settings
+    Options,
 }
@@ -0,0 +1,135 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode, WindowResized},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    consts::{WINDOW_HEIGHT, WINDOW_WIDTH},
+    settings::Settings,
+};
+
+/// Window sizes that can be picked, in pixels
+pub const RESOLUTIONS: [[f32; 2]; 5] = [
+    [800., 600.],
+    [1024., 768.],
+    [1280., 720.],
+    [1600., 900.],
+    [1920., 1080.],
+];
+
+/// Antialiasing samples that can be picked, 1 being no antialiasing
+pub const MSAA_SAMPLES: [u32; 4] = [1, 2, 4, 8];
+
+/// Size and mode of the window. Everything is laid out for a `WINDOW_WIDTH` by `WINDOW_HEIGHT`
+/// window and scaled to fit the actual one, so any size works.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DisplaySettings {
+    /// Window size in pixels, when not in fullscreen
+    pub resolution: [f32; 2],
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Antialiasing samples, one of `MSAA_SAMPLES`
+    pub msaa: u32,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            resolution: [WINDOW_WIDTH, WINDOW_HEIGHT],
+            fullscreen: false,
+            vsync: true,
+            msaa: 4,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Goes to the next resolution, wrapping around to the smallest
+    pub fn next_resolution(&mut self) {
+        let index = RESOLUTIONS
+            .iter()
+            .position(|resolution| *resolution == self.resolution)
+            .map_or(0, |index| (index + 1) % RESOLUTIONS.len());
+        self.resolution = RESOLUTIONS[index];
+    }
+
+    pub fn describe_resolution(&self) -> String {
+        format!("{}x{}", self.resolution[0], self.resolution[1])
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        match self.msaa {
+            1 => Msaa::Off,
+            2 => Msaa::Sample2,
+            8 => Msaa::Sample8,
+            _ => Msaa::Sample4,
+        }
+    }
+}
+
+/// Resizes the window, or makes it fullscreen, when the settings change
+fn apply_display_settings(
+    settings: Res<Settings>,
+    mut msaa: ResMut<Msaa>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let display = settings.display;
+    // only touched when different, so that other settings changing doesn't reset the window
+    if *msaa != display.msaa() {
+        *msaa = display.msaa();
+    }
+    for mut window in windows.iter_mut() {
+        let [width, height] = display.resolution;
+        if window.resolution.width() != width || window.resolution.height() != height {
+            window.resolution.set(width, height);
+        }
+        let mode = if display.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        };
+        if window.mode != mode {
+            window.mode = mode;
+        }
+        if window.present_mode != display.present_mode() {
+            window.present_mode = display.present_mode();
+        }
+    }
+}
+
+fn toggle_fullscreen(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        settings.display.fullscreen = !settings.display.fullscreen;
+    }
+}
+
+/// Scales the HUD and menus with the window, like the camera does with the playfield
+fn scale_ui(mut resized: EventReader<WindowResized>, mut ui_scale: ResMut<UiScale>) {
+    if let Some(event) = resized.read().last() {
+        let scale = (event.width / WINDOW_WIDTH).min(event.height / WINDOW_HEIGHT);
+        ui_scale.0 = scale as f64;
+    }
+}
+
+pub struct DisplayPlugin;
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (toggle_fullscreen, apply_display_settings, scale_ui).chain(),
+        );
+    }
+}
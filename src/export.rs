@@ -0,0 +1,221 @@
+use std::fmt::Write;
+
+use crate::types::*;
+
+/// Rows per beat that arrows are rounded to, which fits 16th notes and triplets. Finer rows
+/// would keep the small timing errors of charts tapped in the Map Maker.
+const ROWS_PER_BEAT: i64 = 12;
+
+const BEATS_PER_MEASURE: i64 = 4;
+
+/// Rows per measure that StepMania can write a measure with, from the coarsest
+const MEASURE_ROWS: [i64; 6] = [4, 8, 12, 16, 24, 48];
+
+/// Formats that charts can be exported to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// StepMania, as a dance-single chart
+    Sm,
+    /// osu!mania with 4 keys
+    Osu,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Sm => "sm",
+            ExportFormat::Osu => "osu",
+        }
+    }
+
+    /// Writes a chart in this format. Only arrow charts with a BPM can be exported.
+    pub fn export(&self, config: &SongConfigToml) -> Result<String, String> {
+        let grid = BeatGrid::new(config)?;
+        Ok(match self {
+            ExportFormat::Sm => to_sm(config, &grid),
+            ExportFormat::Osu => to_osu(config, &grid),
+        })
+    }
+}
+
+/// Column of a lane in both formats, from the left
+fn column(direction: Directions) -> Result<usize, String> {
+    match direction {
+        Directions::Left => Ok(0),
+        Directions::Down => Ok(1),
+        Directions::Up => Ok(2),
+        Directions::Right => Ok(3),
+        _ => Err(format!("{:?} is not an arrow lane", direction)),
+    }
+}
+
+/// A chart's arrows rounded to rows of the beat grid of its BPM
+struct BeatGrid {
+    seconds_per_beat: f64,
+    /// Song time of the first row, in seconds. It's on a beat, and before every arrow.
+    start: f64,
+    /// Row and column of each arrow, in order
+    notes: Vec<(i64, usize)>,
+}
+
+impl BeatGrid {
+    fn new(config: &SongConfigToml) -> Result<BeatGrid, String> {
+        if config.lanes != LaneSet::Arrows {
+            return Err("only arrow charts can be exported".to_string());
+        }
+        let Some(bpm) = config.bpm else {
+            return Err("the chart has no BPM, set one with the bpm command first".to_string());
+        };
+        let seconds_per_beat = 60. / bpm;
+
+        // whole measures before the first beat, so that no arrow is before the first row
+        let measure = seconds_per_beat * BEATS_PER_MEASURE as f64;
+        let mut start = config.offset.unwrap_or(0.);
+        let first_arrow = config
+            .arrows
+            .iter()
+            .map(|arrow| arrow.click_time)
+            .fold(start, f64::min);
+        start -= ((start - first_arrow) / measure).ceil() * measure;
+
+        let mut notes: Vec<(i64, usize)> = config
+            .arrows
+            .iter()
+            .map(|arrow| {
+                let beats = (arrow.click_time - start) / seconds_per_beat;
+                Ok((
+                    (beats * ROWS_PER_BEAT as f64).round() as i64,
+                    column(arrow.direction)?,
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+        notes.sort();
+        notes.dedup();
+
+        Ok(BeatGrid {
+            seconds_per_beat,
+            start,
+            notes,
+        })
+    }
+
+    fn bpm(&self) -> f64 {
+        60. / self.seconds_per_beat
+    }
+
+    /// Song time of a row, in milliseconds
+    fn row_ms(&self, row: i64) -> f64 {
+        1000. * (self.start + row as f64 / ROWS_PER_BEAT as f64 * self.seconds_per_beat)
+    }
+}
+
+fn difficulty_name(difficulty: Option<Difficulty>) -> &'static str {
+    match difficulty {
+        Some(Difficulty::Easy) => "Easy",
+        Some(Difficulty::Medium) | None => "Medium",
+        Some(Difficulty::Hard) => "Hard",
+    }
+}
+
+fn to_sm(config: &SongConfigToml, grid: &BeatGrid) -> String {
+    let meter = match config.difficulty {
+        Some(Difficulty::Easy) => 3,
+        Some(Difficulty::Medium) | None => 5,
+        Some(Difficulty::Hard) => 8,
+    };
+    let mut sm = String::new();
+    writeln!(sm, "#TITLE:{};", config.name).unwrap();
+    writeln!(sm, "#MUSIC:{};", config.filename).unwrap();
+    // StepMania's offset is how far before the music the first beat is
+    writeln!(sm, "#OFFSET:{:.3};", -grid.start).unwrap();
+    writeln!(sm, "#BPMS:0.000={:.3};", grid.bpm()).unwrap();
+    writeln!(sm, "#STOPS:;").unwrap();
+    writeln!(sm, "#NOTES:").unwrap();
+    writeln!(sm, "     dance-single:").unwrap();
+    writeln!(sm, "     :").unwrap();
+    writeln!(sm, "     {}:", difficulty_name(config.difficulty)).unwrap();
+    writeln!(sm, "     {}:", meter).unwrap();
+    writeln!(sm, "     0.000,0.000,0.000,0.000,0.000:").unwrap();
+
+    let measure_rows = ROWS_PER_BEAT * BEATS_PER_MEASURE;
+    let measures = grid
+        .notes
+        .last()
+        .map_or(1, |(row, _)| row / measure_rows + 1);
+    for measure in 0..measures {
+        let first_row = measure * measure_rows;
+        let notes: Vec<(i64, usize)> = grid
+            .notes
+            .iter()
+            .filter(|(row, _)| (first_row..first_row + measure_rows).contains(row))
+            .map(|(row, column)| (row - first_row, *column))
+            .collect();
+        // the coarsest rows that still hold every arrow of the measure
+        let rows = MEASURE_ROWS
+            .iter()
+            .copied()
+            .find(|rows| {
+                notes
+                    .iter()
+                    .all(|(row, _)| row % (measure_rows / rows) == 0)
+            })
+            .unwrap_or(measure_rows);
+        let step = measure_rows / rows;
+        for line in 0..rows {
+            let mut columns = *b"0000";
+            for (_, column) in notes.iter().filter(|(row, _)| *row == line * step) {
+                columns[*column] = b'1';
+            }
+            writeln!(sm, "{}", String::from_utf8_lossy(&columns)).unwrap();
+        }
+        writeln!(sm, "{}", if measure + 1 < measures { "," } else { ";" }).unwrap();
+    }
+    sm
+}
+
+fn to_osu(config: &SongConfigToml, grid: &BeatGrid) -> String {
+    let mut osu = String::new();
+    writeln!(osu, "osu file format v14").unwrap();
+    writeln!(osu).unwrap();
+    writeln!(osu, "[General]").unwrap();
+    writeln!(osu, "AudioFilename: {}", config.filename).unwrap();
+    writeln!(osu, "AudioLeadIn: 0").unwrap();
+    writeln!(osu, "PreviewTime: -1").unwrap();
+    // osu!mania
+    writeln!(osu, "Mode: 3").unwrap();
+    writeln!(osu).unwrap();
+    writeln!(osu, "[Metadata]").unwrap();
+    writeln!(osu, "Title:{}", config.name).unwrap();
+    writeln!(osu, "TitleUnicode:{}", config.name).unwrap();
+    writeln!(osu, "Artist:Unknown").unwrap();
+    writeln!(osu, "ArtistUnicode:Unknown").unwrap();
+    writeln!(osu, "Creator:Drum City").unwrap();
+    writeln!(osu, "Version:{}", difficulty_name(config.difficulty)).unwrap();
+    writeln!(osu).unwrap();
+    writeln!(osu, "[Difficulty]").unwrap();
+    writeln!(osu, "HPDrainRate:5").unwrap();
+    // the number of keys
+    writeln!(osu, "CircleSize:4").unwrap();
+    writeln!(osu, "OverallDifficulty:5").unwrap();
+    writeln!(osu, "ApproachRate:5").unwrap();
+    writeln!(osu, "SliderMultiplier:1.4").unwrap();
+    writeln!(osu, "SliderTickRate:1").unwrap();
+    writeln!(osu).unwrap();
+    writeln!(osu, "[TimingPoints]").unwrap();
+    writeln!(
+        osu,
+        "{},{},{},1,0,100,1,0",
+        grid.row_ms(0).round(),
+        1000. * grid.seconds_per_beat,
+        BEATS_PER_MEASURE
+    )
+    .unwrap();
+    writeln!(osu).unwrap();
+    writeln!(osu, "[HitObjects]").unwrap();
+    for (row, column) in grid.notes.iter() {
+        // osu!mania picks the column from x, across a 512 wide playfield
+        let x = (*column as i64 * 2 + 1) * 512 / 8;
+        writeln!(osu, "{},192,{},1,0,0:0:0:0:", x, grid.row_ms(*row).round()).unwrap();
+    }
+    osu
+}
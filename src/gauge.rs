@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    arrows::JudgmentSet,
+    consts::AppState,
+    practice::Practice,
+    score::{ArrowJudged, Judgment, Score},
+    types::{Difficulty, SongConfig},
+};
+
+/// Letter grade of a run, from the share of the chart's points that were earned
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Grade {
+    S,
+    A,
+    B,
+    C,
+    D,
+}
+
+/// How forgiving the life gauge and the grades are on one difficulty
+#[derive(Copy, Clone, Debug)]
+pub struct GaugeSettings {
+    /// Life at the start of a song, from 0 to 1
+    pub start: f32,
+    pub perfect: f32,
+    pub great: f32,
+    pub good: f32,
+    /// Life lost on a miss
+    pub miss: f32,
+    /// Lowest accuracy for S, A, B and C. Anything below is a D.
+    pub grades: [f32; 4],
+}
+
+impl GaugeSettings {
+    pub fn for_difficulty(difficulty: Difficulty) -> GaugeSettings {
+        match difficulty {
+            Difficulty::Easy => GaugeSettings {
+                start: 0.6,
+                perfect: 0.04,
+                great: 0.03,
+                good: 0.02,
+                miss: 0.08,
+                grades: [0.95, 0.85, 0.7, 0.55],
+            },
+            Difficulty::Medium => GaugeSettings {
+                start: 0.5,
+                perfect: 0.03,
+                great: 0.02,
+                good: 0.01,
+                miss: 0.1,
+                grades: [0.93, 0.8, 0.65, 0.5],
+            },
+            Difficulty::Hard => GaugeSettings {
+                start: 0.5,
+                perfect: 0.02,
+                great: 0.015,
+                good: 0.,
+                miss: 0.12,
+                grades: [0.9, 0.75, 0.6, 0.45],
+            },
+        }
+    }
+
+    fn change(&self, judgment: Judgment) -> f32 {
+        match judgment {
+            Judgment::Perfect => self.perfect,
+            Judgment::Great => self.great,
+            Judgment::Good => self.good,
+            Judgment::Miss => -self.miss,
+        }
+    }
+
+    pub fn grade(&self, accuracy: f32) -> Grade {
+        let [s, a, b, c] = self.grades;
+        if accuracy >= s {
+            Grade::S
+        } else if accuracy >= a {
+            Grade::A
+        } else if accuracy >= b {
+            Grade::B
+        } else if accuracy >= c {
+            Grade::C
+        } else {
+            Grade::D
+        }
+    }
+}
+
+/// Life of the song being played. The song is failed when it runs out.
+#[derive(Resource, Debug)]
+pub struct LifeGauge {
+    /// From 0 to 1
+    pub life: f32,
+    pub failed: bool,
+    pub settings: GaugeSettings,
+    /// Number of arrows in the chart, to grade against
+    pub total_arrows: usize,
+}
+
+impl Default for LifeGauge {
+    fn default() -> Self {
+        let settings = GaugeSettings::for_difficulty(Difficulty::Medium);
+        Self {
+            life: settings.start,
+            failed: false,
+            settings,
+            total_arrows: 0,
+        }
+    }
+}
+
+impl LifeGauge {
+    /// Share of the chart's points earned so far. Arrows that were never played count as misses.
+    pub fn accuracy(&self, score: &Score) -> f32 {
+        if self.total_arrows == 0 {
+            return 1.;
+        }
+        score.get_points() as f32 / (100 * self.total_arrows) as f32
+    }
+
+    pub fn grade(&self, score: &Score) -> Grade {
+        self.settings.grade(self.accuracy(score))
+    }
+
+    /// Accuracy of only the arrows judged so far, for a grade while the song is being played
+    pub fn running_grade(&self, score: &Score) -> Grade {
+        let judged = score.get_corrects() + score.get_fails();
+        if judged == 0 {
+            return Grade::S;
+        }
+        self.settings
+            .grade(score.get_points() as f32 / (100 * judged) as f32)
+    }
+}
+
+fn reset_life_gauge(mut gauge: ResMut<LifeGauge>, song_config: Res<SongConfig>) {
+    // charts without a difficulty are treated as medium
+    let settings =
+        GaugeSettings::for_difficulty(song_config.difficulty.unwrap_or(Difficulty::Medium));
+    *gauge = LifeGauge {
+        life: settings.start,
+        failed: false,
+        settings,
+        total_arrows: song_config.arrows.len(),
+    };
+}
+
+fn update_life_gauge(
+    mut gauge: ResMut<LifeGauge>,
+    mut judged: EventReader<ArrowJudged>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    for event in judged.read() {
+        if gauge.failed {
+            break;
+        }
+        let change = gauge.settings.change(event.judgment);
+        gauge.life = (gauge.life + change).clamp(0., 1.);
+
+        // with some slack, since the changes don't add up exactly in floating point
+        if gauge.life < 0.001 {
+            gauge.failed = true;
+            app_state.set(AppState::Results);
+        }
+    }
+}
+
+pub struct GaugePlugin;
+impl Plugin for GaugePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LifeGauge>()
+            .add_systems(OnEnter(AppState::Game), reset_life_gauge)
+            .add_systems(
+                Update,
+                update_life_gauge
+                    .after(JudgmentSet)
+                    .run_if(in_state(AppState::Game))
+                    // practice can't be failed
+                    .run_if(not(resource_exists::<Practice>())),
+            );
+    }
+}
@@ -0,0 +1,275 @@
+use bevy::{
+    asset::LoadState,
+    input::{keyboard::KeyboardInput, ButtonState},
+    prelude::*,
+    utils::Duration,
+};
+
+use crate::{
+    arrows::{Arrow, ArrowsPlugin},
+    autoplay::{Autoplay, AutoplayPlugin},
+    chart::{Chart, ChartPlugin},
+    consts::{AppState, START_TIME_OFFSET},
+    gauge::{GaugePlugin, LifeGauge},
+    input::InputPlugin,
+    midi::{MidiInput, MidiLoopback, MidiPlugin},
+    modifiers::{Modifiers, ModifiersPlugin},
+    practice::{Practice, PracticePlugin},
+    replay::{Replay, ReplayPlayback, ReplayPlugin, ReplayRecorder},
+    score::Score,
+    settings::Settings,
+    time::{ControlledTime, TimePlugin},
+    types::{Directions, SongConfig},
+};
+
+/// Length of one simulated frame
+pub const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Builds an app that runs the gameplay without a window, audio or rendering. Its
+/// `ControlledTime` is a manual clock, so simulations are deterministic.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        bevy::input::InputPlugin,
+    ))
+    // arrows, songs and text hold handles to these, even though nothing is drawn or played
+    .init_asset::<Image>()
+    .init_asset::<AudioSource>()
+    .init_asset::<Font>()
+    .insert_resource(Score::new())
+    // default settings, whatever the player saved
+    .init_resource::<Settings>()
+    .add_state::<AppState>()
+    .add_plugins((
+        ChartPlugin,
+        TimePlugin,
+        InputPlugin,
+        AutoplayPlugin,
+        ModifiersPlugin,
+        ArrowsPlugin,
+        GaugePlugin,
+        PracticePlugin,
+    ));
+
+    app.world
+        .resource_mut::<ControlledTime>()
+        .use_manual_clock();
+
+    // MIDI comes from the loopback instead of a device
+    let (midi_input, midi_loopback) = MidiInput::loopback();
+    app.insert_resource(midi_input)
+        .insert_resource(midi_loopback)
+        .add_plugins(MidiPlugin);
+    app
+}
+
+/// Loads a chart from `assets/songs` through the app's `AssetServer`, running the app until it
+/// has loaded
+pub fn load_song(app: &mut App, chart: &str) -> SongConfig {
+    let handle: Handle<Chart> = app
+        .world
+        .resource::<AssetServer>()
+        .load(format!("songs/{}", chart));
+    loop {
+        if let Some(loaded) = app.world.resource::<Assets<Chart>>().get(&handle) {
+            let asset_server = app.world.resource::<AssetServer>();
+            return SongConfig::from_chart(chart, handle.clone(), loaded, asset_server);
+        }
+        let load_state = app.world.resource::<AssetServer>().get_load_state(&handle);
+        if load_state == Some(LoadState::Failed) {
+            panic!("Could not load chart {}", chart);
+        }
+        app.update();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Plays a chart in a headless app, with scripted key presses at chosen song times
+pub struct Simulation {
+    pub app: App,
+}
+
+impl Simulation {
+    /// Loads a chart from `assets/songs` and starts playing it
+    pub fn new(chart: &str) -> Self {
+        Self::with_resource(chart, Modifiers::default())
+    }
+
+    /// Starts playing a chart with a resource that changes how it's played, e.g. `Modifiers`,
+    /// `ScrollSpeed` or `StartPosition`
+    pub fn with_resource<R: Resource>(chart: &str, resource: R) -> Self {
+        let mut app = headless_app();
+        app.insert_resource(resource);
+        Self::start(app, chart)
+    }
+
+    /// Plays a chart while recording a replay of it, see `recorded_replay`. Like in the game,
+    /// the replay is saved once the song is over.
+    pub fn recording(chart: &str) -> Self {
+        let mut app = headless_app();
+        app.add_plugins(ReplayPlugin);
+        Self::start(app, chart)
+    }
+
+    /// Watches a replay of a chart, which presses the lanes instead of the keyboard
+    pub fn watch(chart: &str, replay: Replay) -> Self {
+        let mut app = headless_app();
+        app.add_plugins(ReplayPlugin);
+        let mut modifiers = Modifiers::default();
+        let playback = ReplayPlayback::new(replay, &mut modifiers);
+        app.insert_resource(modifiers).insert_resource(playback);
+        Self::start(app, chart)
+    }
+
+    fn start(mut app: App, chart: &str) -> Self {
+        let config = load_song(&mut app, chart);
+        app.insert_resource(config);
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Game);
+        app.update();
+
+        Self { app }
+    }
+
+    /// Loops a section of a chart in practice mode, from `start` to `end` in chart seconds
+    pub fn practice(chart: &str, start: f64, end: f64) -> Self {
+        let mut app = headless_app();
+        let config = load_song(&mut app, chart);
+        app.insert_resource(Practice::new(&config).with_section(start, end));
+        app.insert_resource(config);
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Game);
+        app.update();
+
+        Self { app }
+    }
+
+    pub fn arrows_on_screen(&mut self) -> usize {
+        self.app
+            .world
+            .query::<&Arrow>()
+            .iter(&self.app.world)
+            .count()
+    }
+
+    /// Replay of the song so far, in a simulation that's `recording`
+    pub fn recorded_replay(&self) -> Replay {
+        let world = &self.app.world;
+        Replay::recorded(
+            world.resource::<ReplayRecorder>(),
+            world.resource::<SongConfig>(),
+            world.resource::<Score>(),
+            world.resource::<Modifiers>(),
+        )
+    }
+
+    pub fn practice_state(&self) -> &Practice {
+        self.app.world.resource::<Practice>()
+    }
+
+    /// Presses and releases a key that isn't a lane, like the practice keys
+    pub fn tap_key(&mut self, key: KeyCode) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            self.app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+            self.app.update();
+        }
+    }
+
+    /// Lets the autoplay bot play the rest of the song
+    pub fn enable_autoplay(&mut self) {
+        self.app.world.resource_mut::<Autoplay>().enabled = true;
+    }
+
+    /// Position in the song in seconds
+    pub fn song_time(&self) -> f64 {
+        self.app
+            .world
+            .resource::<ControlledTime>()
+            .song_seconds_f64()
+    }
+
+    /// Runs frames until the song reaches `song_time`, or until the song is over. The last frame
+    /// is shortened so that the song lands exactly on that time.
+    pub fn run_until(&mut self, song_time: f64) {
+        let target = Duration::from_secs_f64(song_time + START_TIME_OFFSET as f64);
+        while self.state() == AppState::Game {
+            let elapsed = self.app.world.resource::<ControlledTime>().elapsed();
+            if elapsed >= target {
+                break;
+            }
+            let step = (target - elapsed).min(FRAME);
+            self.app
+                .world
+                .resource_mut::<ControlledTime>()
+                .advance(step);
+            self.app.update();
+        }
+    }
+
+    /// Runs frames for `seconds` of song time
+    pub fn run_for(&mut self, seconds: f64) {
+        self.run_until(self.song_time() + seconds);
+    }
+
+    /// Runs a number of frames, wherever the song clock goes. Practice loops jump back in the
+    /// song, so they never reach a song time.
+    pub fn run_frames(&mut self, count: usize) {
+        for _ in 0..count {
+            self.app
+                .world
+                .resource_mut::<ControlledTime>()
+                .advance(FRAME);
+            self.app.update();
+        }
+    }
+
+    /// Presses the keys of `directions` together at the current song time, then releases them
+    pub fn press(&mut self, directions: &[Directions]) {
+        self.send_keys(directions, ButtonState::Pressed);
+        self.app.update();
+        self.send_keys(directions, ButtonState::Released);
+        self.app.update();
+    }
+
+    /// Plays MIDI notes together at the current song time, as `(note, velocity)`
+    pub fn play_notes(&mut self, notes: &[(u8, u8)]) {
+        let loopback = self.app.world.resource::<MidiLoopback>().clone();
+        for (note, velocity) in notes {
+            loopback.note_on(*note, *velocity);
+        }
+        self.app.update();
+    }
+
+    fn send_keys(&mut self, directions: &[Directions], state: ButtonState) {
+        let key_bindings = self.app.world.resource::<Settings>().key_bindings;
+        for direction in directions {
+            self.app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_bindings.key(*direction)),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+        }
+    }
+
+    pub fn state(&self) -> AppState {
+        *self.app.world.resource::<State<AppState>>().get()
+    }
+
+    pub fn score(&self) -> &Score {
+        self.app.world.resource::<Score>()
+    }
+
+    pub fn life_gauge(&self) -> &LifeGauge {
+        self.app.world.resource::<LifeGauge>()
+    }
+}
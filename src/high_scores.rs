@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    arrows::StartPosition,
+    autoplay::Autoplay,
+    consts::{AppState, HIGH_SCORES_PER_CHART, SCORES_FILE},
+    gauge::{Grade, LifeGauge},
+    modifiers::Modifiers,
+    replay::ReplayPlayback,
+    score::Score,
+    storage,
+    time::unix_time,
+    types::{Difficulty, SongConfig},
+};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub score: usize,
+    pub corrects: usize,
+    pub fails: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grade: Option<Grade>,
+    /// Whether the life gauge ran out before the end of the song
+    #[serde(default)]
+    pub failed: bool,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+    /// Seconds since the Unix epoch
+    pub played_at: u64,
+}
+
+/// Best scores on one version of a chart, best first. Runs that failed come after every run
+/// that cleared.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChartScores {
+    /// Chart file in `assets/songs` when the scores were set
+    pub chart: String,
+    pub name: String,
+    pub difficulty: Option<Difficulty>,
+    pub scores: Vec<HighScore>,
+}
+
+/// Local high scores of every chart, keyed by chart hash (see `types::chart_hash`), so that
+/// editing a chart starts its scores over
+#[derive(Resource, Serialize, Deserialize, Debug, Default)]
+pub struct HighScores {
+    pub charts: HashMap<String, ChartScores>,
+}
+
+impl HighScores {
+    /// Reads the save file, or starts empty if there is none yet. A file that can't be parsed is
+    /// set aside rather than overwritten.
+    pub fn load() -> HighScores {
+        match storage::read(SCORES_FILE) {
+            Some(contents) => toml::from_str(&contents).unwrap_or_else(|error| {
+                let broken = storage::set_aside(SCORES_FILE);
+                warn!(
+                    "Could not parse {}, so the scores start over. It was kept as {}: {}",
+                    SCORES_FILE, broken, error
+                );
+                HighScores::default()
+            }),
+            None => HighScores::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string(self).expect("Couldn't convert scores to toml text");
+        storage::write(SCORES_FILE, &text);
+    }
+
+    /// Moves a chart's scores to a new hash, for when its file was rewritten without changing
+    /// how it plays. Returns `false` if it had no scores.
+    pub fn rehash(&mut self, old_hash: &str, new_hash: &str) -> bool {
+        let Some(scores) = self.charts.remove(old_hash) else {
+            return false;
+        };
+        self.charts.insert(new_hash.to_string(), scores);
+        true
+    }
+
+    /// Best run that cleared the chart. Failed runs are never a personal best.
+    pub fn personal_best(&self, chart_hash: &str) -> Option<&HighScore> {
+        self.charts
+            .get(chart_hash)
+            .and_then(|chart| chart.scores.iter().find(|score| !score.failed))
+    }
+
+    /// Adds a score to a chart's table, keeping only the best ones. Returns `true` if it is a new
+    /// personal best, which a failed run never is.
+    pub fn add(&mut self, song_config: &SongConfig, score: HighScore) -> bool {
+        let previous_best = self.personal_best(&song_config.hash).map(|best| best.score);
+
+        let chart = self.charts.entry(song_config.hash.clone()).or_default();
+        chart.chart = song_config.path.clone();
+        chart.name = song_config.name.clone();
+        chart.difficulty = song_config.difficulty;
+
+        // ties keep the older score first
+        let position = chart
+            .scores
+            .iter()
+            .position(|other| {
+                (other.failed && !score.failed)
+                    || (other.failed == score.failed && other.score < score.score)
+            })
+            .unwrap_or(chart.scores.len());
+        chart.scores.insert(position, score);
+        chart.scores.truncate(HIGH_SCORES_PER_CHART);
+
+        !score.failed && previous_best.map_or(true, |best| score.score > best)
+    }
+}
+
+/// How the song that was just played compares to the personal best, for the results screen
+#[derive(Resource, Debug)]
+pub struct LastResult {
+    /// Personal best before this run
+    pub previous_best: Option<usize>,
+    pub new_best: bool,
+    /// Runs with autoplay, replays and songs started part way through don't count towards high
+    /// scores
+    pub counted: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn record_score(
+    mut commands: Commands,
+    mut high_scores: ResMut<HighScores>,
+    song_config: Res<SongConfig>,
+    score: Res<Score>,
+    gauge: Res<LifeGauge>,
+    modifiers: Res<Modifiers>,
+    autoplay: Res<Autoplay>,
+    playback: Option<Res<ReplayPlayback>>,
+    start: Option<Res<StartPosition>>,
+) {
+    let previous_best = high_scores
+        .personal_best(&song_config.hash)
+        .map(|best| best.score);
+    let counted = !autoplay.used && playback.is_none() && start.is_none();
+
+    let mut new_best = false;
+    if counted {
+        let played_at = unix_time().as_secs();
+        new_best = high_scores.add(
+            &song_config,
+            HighScore {
+                score: score.get_score(),
+                corrects: score.get_corrects(),
+                fails: score.get_fails(),
+                grade: Some(gauge.grade(&score)),
+                failed: gauge.failed,
+                modifiers: *modifiers,
+                played_at,
+            },
+        );
+        high_scores.save();
+    }
+
+    commands.insert_resource(LastResult {
+        previous_best,
+        new_best,
+        counted,
+    });
+}
+
+pub struct HighScoresPlugin;
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load())
+            // only finished songs reach the results
+            .add_systems(OnEnter(AppState::Results), record_score);
+    }
+}
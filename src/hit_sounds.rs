@@ -0,0 +1,210 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    arrows::JudgmentSet,
+    audio::{play_sound, SoundChannel},
+    consts::AppState,
+    input::{LaneInputSet, LanePressed},
+    score::{ArrowJudged, Judgment},
+    settings::Settings,
+    types::{Directions, SongConfig, DRUM_LANES},
+};
+
+/// Which sounds play when an arrow is hit
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HitSoundMode {
+    /// The arrow's keysound if the chart gives it one, the hit sound otherwise
+    On,
+    /// Only keysounds, arrows without one are silent
+    KeysoundsOnly,
+    Off,
+}
+
+/// Hit sounds replacing the judgment ones in a lane, files in `assets/sounds`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LaneSounds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<String>,
+}
+
+impl LaneSounds {
+    pub fn sound(&self, direction: Directions) -> Option<&str> {
+        match direction {
+            Directions::Up => self.up.as_deref(),
+            Directions::Down => self.down.as_deref(),
+            Directions::Left => self.left.as_deref(),
+            Directions::Right => self.right.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Sounds played on hits, as files in `assets/sounds`. Misses are silent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct HitSoundSettings {
+    pub mode: HitSoundMode,
+    pub perfect: String,
+    pub great: String,
+    pub good: String,
+    pub lanes: LaneSounds,
+}
+
+impl Default for HitSoundSettings {
+    fn default() -> Self {
+        Self {
+            mode: HitSoundMode::On,
+            perfect: "hit_perfect.wav".to_string(),
+            great: "hit_great.wav".to_string(),
+            good: "hit_good.wav".to_string(),
+            lanes: LaneSounds::default(),
+        }
+    }
+}
+
+impl HitSoundSettings {
+    /// Hit sound for a judgment in a lane, if one plays. A lane's own sound wins over the
+    /// judgment's. Drum lanes play their sample when pressed instead.
+    pub fn sound(&self, direction: Directions, judgment: Judgment) -> Option<&str> {
+        if self.mode != HitSoundMode::On || direction.is_drum() {
+            return None;
+        }
+        let judgment_sound = match judgment {
+            Judgment::Perfect => &self.perfect,
+            Judgment::Great => &self.great,
+            Judgment::Good => &self.good,
+            Judgment::Miss => return None,
+        };
+        Some(self.lanes.sound(direction).unwrap_or(judgment_sound))
+    }
+
+    pub fn plays_keysounds(&self) -> bool {
+        self.mode != HitSoundMode::Off
+    }
+
+    /// Goes to the next mode, wrapping around to `On`
+    pub fn next_mode(&mut self) {
+        self.mode = match self.mode {
+            HitSoundMode::On => HitSoundMode::KeysoundsOnly,
+            HitSoundMode::KeysoundsOnly => HitSoundMode::Off,
+            HitSoundMode::Off => HitSoundMode::On,
+        };
+    }
+
+    pub fn describe_mode(&self) -> &'static str {
+        match self.mode {
+            HitSoundMode::On => "on",
+            HitSoundMode::KeysoundsOnly => "keysounds only",
+            HitSoundMode::Off => "off",
+        }
+    }
+
+    /// Every file a hit sound or drum sample may be played from
+    fn files(&self) -> Vec<&str> {
+        let lanes = &self.lanes;
+        let mut files: Vec<&str> = [&self.perfect, &self.great, &self.good]
+            .into_iter()
+            .map(String::as_str)
+            .chain(
+                [&lanes.up, &lanes.down, &lanes.left, &lanes.right]
+                    .into_iter()
+                    .filter_map(|sound| sound.as_deref()),
+            )
+            .collect();
+        for sample in DRUM_LANES.iter().filter_map(Directions::drum_sample) {
+            files.push(sample);
+        }
+        files
+    }
+}
+
+/// Hit sounds by file name, loaded when a song starts so that the first hits aren't late
+#[derive(Resource, Default)]
+struct HitSoundHandles(HashMap<String, Handle<AudioSource>>);
+
+fn load_hit_sounds(
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    mut handles: ResMut<HitSoundHandles>,
+) {
+    for file in settings.hit_sounds.files() {
+        if !handles.0.contains_key(file) {
+            let handle = asset_server.load(format!("sounds/{}", file));
+            handles.0.insert(file.to_string(), handle);
+        }
+    }
+}
+
+fn play_hit_sounds(
+    mut commands: Commands,
+    mut judged: EventReader<ArrowJudged>,
+    song_config: Res<SongConfig>,
+    settings: Res<Settings>,
+    handles: Res<HitSoundHandles>,
+) {
+    let hit_sounds = &settings.hit_sounds;
+    for event in judged.read() {
+        if event.judgment == Judgment::Miss {
+            continue;
+        }
+        let keysound = event
+            .keysound
+            .filter(|_| hit_sounds.plays_keysounds())
+            .and_then(|index| song_config.keysounds.get(index));
+        let source = match keysound {
+            Some(keysound) => Some(keysound),
+            None => hit_sounds
+                .sound(event.direction, event.judgment)
+                .and_then(|file| handles.0.get(file)),
+        };
+        if let Some(source) = source {
+            play_sound(&mut commands, &settings, source.clone(), SoundChannel::Hits);
+        }
+    }
+}
+
+/// Plays the drum kit: every press of a drum lane sounds, whether it hits a note or not
+fn play_drum_sounds(
+    mut commands: Commands,
+    mut presses: EventReader<LanePressed>,
+    settings: Res<Settings>,
+    handles: Res<HitSoundHandles>,
+) {
+    if settings.hit_sounds.mode == HitSoundMode::Off {
+        presses.clear();
+        return;
+    }
+    for press in presses.read() {
+        let source = press
+            .direction
+            .drum_sample()
+            .and_then(|file| handles.0.get(file));
+        if let Some(source) = source {
+            play_sound(&mut commands, &settings, source.clone(), SoundChannel::Hits);
+        }
+    }
+}
+
+pub struct HitSoundsPlugin;
+impl Plugin for HitSoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitSoundHandles>()
+            .add_systems(OnEnter(AppState::Game), load_hit_sounds)
+            .add_systems(
+                Update,
+                (
+                    play_drum_sounds.after(LaneInputSet),
+                    play_hit_sounds.after(JudgmentSet),
+                )
+                    .run_if(in_state(AppState::Game)),
+            );
+    }
+}
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    autoplay::autoplay_enabled,
+    consts::AppState,
+    replay::ReplayPlayback,
+    settings::Settings,
+    time::ControlledTime,
+    types::{Directions, SongConfig, ARROW_LANES, DRUM_LANES},
+};
+
+/// Keys that do something while lanes are played, and so can't play a lane: quitting, going back,
+/// autoplay, volume and fullscreen everywhere, and the practice and Map Maker controls
+pub const RESERVED_KEYS: [KeyCode; 29] = [
+    KeyCode::Escape,
+    KeyCode::Back,
+    KeyCode::F2,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F11,
+    // practice
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::C,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::R,
+    // Map Maker
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Tab,
+    KeyCode::Return,
+    KeyCode::T,
+    KeyCode::Z,
+    KeyCode::X,
+    KeyCode::S,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+/// Key that plays each lane
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub kick: KeyCode,
+    pub snare: KeyCode,
+    pub hihat: KeyCode,
+    pub tom: KeyCode,
+    pub crash: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: KeyCode::Up,
+            down: KeyCode::Down,
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+            // across the keyboard like the pieces across the kit
+            hihat: KeyCode::D,
+            snare: KeyCode::F,
+            kick: KeyCode::Space,
+            tom: KeyCode::J,
+            crash: KeyCode::K,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Key that plays this direction
+    pub fn key(&self, direction: Directions) -> KeyCode {
+        match direction {
+            Directions::Up => self.up,
+            Directions::Down => self.down,
+            Directions::Left => self.left,
+            Directions::Right => self.right,
+            Directions::Kick => self.kick,
+            Directions::Snare => self.snare,
+            Directions::HiHat => self.hihat,
+            Directions::Tom => self.tom,
+            Directions::Crash => self.crash,
+        }
+    }
+
+    pub fn set(&mut self, direction: Directions, key: KeyCode) {
+        match direction {
+            Directions::Up => self.up = key,
+            Directions::Down => self.down = key,
+            Directions::Left => self.left = key,
+            Directions::Right => self.right = key,
+            Directions::Kick => self.kick = key,
+            Directions::Snare => self.snare = key,
+            Directions::HiHat => self.hihat = key,
+            Directions::Tom => self.tom = key,
+            Directions::Crash => self.crash = key,
+        }
+    }
+
+    /// Binds a key to a direction, unless the key is reserved or already plays another lane of the
+    /// same kind. Arrow and drum lanes can share keys, as a chart only has one kind.
+    pub fn bind(&mut self, direction: Directions, key: KeyCode) -> Result<(), String> {
+        if RESERVED_KEYS.contains(&key) {
+            return Err(format!("{:?} is reserved", key));
+        }
+        let lanes: &[Directions] = if ARROW_LANES.contains(&direction) {
+            &ARROW_LANES
+        } else {
+            &DRUM_LANES
+        };
+        if let Some(other) = lanes
+            .iter()
+            .find(|&&other| other != direction && self.key(other) == key)
+        {
+            return Err(format!("{:?} plays {:?}", key, other));
+        }
+        self.set(direction, key);
+        Ok(())
+    }
+
+    /// Checks if the key of this direction was just pressed
+    pub fn just_pressed(&self, direction: Directions, input: &Input<KeyCode>) -> bool {
+        input.just_pressed(self.key(direction))
+    }
+
+    /// Checks if the key of this direction is currently being pressed
+    pub fn pressed(&self, direction: Directions, input: &Input<KeyCode>) -> bool {
+        input.pressed(self.key(direction))
+    }
+}
+
+/// A lane was pressed during a song. Everything that plays the game (the keyboard, replays,
+/// autoplay) sends these, and `arrows` judges them.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct LanePressed {
+    pub direction: Directions,
+    /// Song time of the press, in seconds
+    pub time: f64,
+    /// How hard a MIDI pad was hit, from 1 to 127. Keys don't have one.
+    pub velocity: Option<u8>,
+}
+
+/// Systems that send `LanePressed` events. Judging runs after these, so a press is judged on the
+/// frame it happened.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LaneInputSet;
+
+fn keyboard_lane_presses(
+    time: Res<ControlledTime>,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    song_config: Res<SongConfig>,
+    mut presses: EventWriter<LanePressed>,
+) {
+    // only the chart's lanes, so that drum keys can share keys with the arrows
+    for &direction in song_config.lanes.lanes() {
+        if settings
+            .key_bindings
+            .just_pressed(direction, &keyboard_input)
+        {
+            presses.send(LanePressed {
+                direction,
+                time: time.song_seconds_f64() - settings.input_offset,
+                velocity: None,
+            });
+        }
+    }
+}
+
+pub struct InputPlugin;
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LanePressed>().add_systems(
+            Update,
+            keyboard_lane_presses
+                .in_set(LaneInputSet)
+                .run_if(in_state(AppState::Game))
+                .run_if(not(resource_exists::<ReplayPlayback>()))
+                .run_if(not(autoplay_enabled)),
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    consts::TARGET_POSITION,
+    types::{Directions, DRUM_LANES},
+};
+
+/// Which way arrows travel across the screen
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    /// From left to right, with the lanes stacked on top of each other
+    Horizontal,
+    /// From the bottom up to targets at the top, with the lanes side by side
+    Upscroll,
+    /// From the top down to targets at the bottom, with the lanes side by side
+    Downscroll,
+}
+
+/// Describes where the playfield is drawn. Arrows only know how far along their path they are,
+/// from `SPAWN_POSITION` to `TARGET_POSITION` and beyond, and the layout turns that into a place
+/// on the screen. Timing and judging don't depend on it.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Layout {
+    pub orientation: Orientation,
+    /// Arrow lanes in order across the playfield, from the top or from the left. Drum lanes are
+    /// always in the order of `DRUM_LANES`.
+    pub lanes: [Directions; 4],
+    /// Distance between the middles of two neighbouring lanes
+    pub lane_spacing: f32,
+    /// Moves the lanes across the playfield, away from the middle of the screen
+    pub lane_offset: f32,
+    /// Distance from the middle of the screen to the targets, towards where arrows go
+    pub target_distance: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::horizontal()
+    }
+}
+
+impl Layout {
+    pub fn horizontal() -> Self {
+        Self {
+            orientation: Orientation::Horizontal,
+            lanes: [
+                Directions::Up,
+                Directions::Down,
+                Directions::Left,
+                Directions::Right,
+            ],
+            lane_spacing: 100.,
+            lane_offset: 0.,
+            target_distance: TARGET_POSITION,
+        }
+    }
+
+    /// Arrows rise to targets at the top, with the lanes in the usual dance mat order
+    pub fn upscroll() -> Self {
+        Self {
+            orientation: Orientation::Upscroll,
+            lanes: [
+                Directions::Left,
+                Directions::Down,
+                Directions::Up,
+                Directions::Right,
+            ],
+            ..Self::horizontal()
+        }
+    }
+
+    pub fn downscroll() -> Self {
+        Self {
+            orientation: Orientation::Downscroll,
+            ..Self::upscroll()
+        }
+    }
+
+    /// Goes to the next orientation, with its usual lanes
+    pub fn next(&self) -> Layout {
+        match self.orientation {
+            Orientation::Horizontal => Self::upscroll(),
+            Orientation::Upscroll => Self::downscroll(),
+            Orientation::Downscroll => Self::horizontal(),
+        }
+    }
+
+    /// Position of a lane across the playfield, the first lane being at the top or the left
+    pub fn lane_position(&self, direction: Directions) -> f32 {
+        let (index, count) = if direction.is_drum() {
+            (direction.lane(), DRUM_LANES.len())
+        } else {
+            let index = self
+                .lanes
+                .iter()
+                .position(|lane| *lane == direction)
+                .unwrap_or_else(|| direction.lane());
+            (index, self.lanes.len())
+        };
+        let middle = (count - 1) as f32 / 2.;
+        let position = (index as f32 - middle) * self.lane_spacing + self.lane_offset;
+        match self.orientation {
+            // the first lane is at the top, where y is highest
+            Orientation::Horizontal => -position,
+            Orientation::Upscroll | Orientation::Downscroll => position,
+        }
+    }
+
+    /// Place on the screen of an arrow that is `position` along its path
+    pub fn translation(&self, direction: Directions, position: f32) -> Vec3 {
+        let along = self.target_distance + position - TARGET_POSITION;
+        let across = self.lane_position(direction);
+        match self.orientation {
+            Orientation::Horizontal => Vec3::new(along, across, 1.),
+            Orientation::Upscroll => Vec3::new(across, along, 1.),
+            Orientation::Downscroll => Vec3::new(across, -along, 1.),
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self.orientation {
+            Orientation::Horizontal => "Horizontal",
+            Orientation::Upscroll => "Upscroll",
+            Orientation::Downscroll => "Downscroll",
+        }
+    }
+}
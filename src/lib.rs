@@ -0,0 +1,36 @@
+pub mod analysis;
+pub mod arrows;
+pub mod audio;
+pub mod autoplay;
+pub mod chart;
+pub mod chart_gen;
+pub mod cli;
+pub mod consts;
+pub mod debug;
+pub mod display;
+pub mod export;
+pub mod gauge;
+pub mod headless;
+pub mod high_scores;
+pub mod hit_sounds;
+pub mod input;
+pub mod layout;
+pub mod map_maker;
+pub mod menu;
+pub mod midi;
+pub mod midi_import;
+pub mod migrations;
+pub mod modifiers;
+pub mod options;
+pub mod practice;
+pub mod replay;
+pub mod results;
+pub mod score;
+pub mod scroll;
+pub mod settings;
+pub mod shaders;
+pub mod storage;
+pub mod stretch;
+pub mod time;
+pub mod types;
+pub mod ui;
@@ -1,34 +1,17 @@
 use bevy::{
     prelude::*,
-    window::{PresentMode, WindowResolution},
+    render::camera::ScalingMode,
+    window::{WindowMode, WindowResolution},
 };
 
-mod arrows;
-mod debug;
-mod map_maker;
-mod menu;
-mod time;
-use arrows::ArrowsPlugin;
-
-mod score;
-use consts::*;
-use debug::DebugPlugin;
-use map_maker::MapMakerPlugin;
-use menu::MenuPlugin;
-use score::Score;
-
-mod ui;
-use time::TimePlugin;
-use ui::UIPlugin;
-
-mod audio;
-use audio::AudioPlugin;
-
-mod consts;
-mod types;
-
-mod shaders;
-use shaders::ShadersPlugin;
+use drum_city::{
+    arrows::ArrowsPlugin, audio::AudioPlugin, autoplay::AutoplayPlugin, chart::ChartPlugin, cli,
+    consts::*, debug::DebugPlugin, display::DisplayPlugin, gauge::GaugePlugin,
+    high_scores::HighScoresPlugin, hit_sounds::HitSoundsPlugin, input::InputPlugin,
+    map_maker::MapMakerPlugin, menu::MenuPlugin, midi::MidiPlugin, modifiers::ModifiersPlugin,
+    options::OptionsPlugin, practice::PracticePlugin, replay::ReplayPlugin, results::ResultsPlugin,
+    score::Score, settings::Settings, shaders::ShadersPlugin, time::TimePlugin, ui::UIPlugin,
+};
 
 #[derive(Component)]
 struct Person;
@@ -44,18 +27,35 @@ fn main() {
     #[cfg(target_arch = "wasm32")]
     console_error_panic_hook::set_once();
 
-    App::new()
-        .add_systems(Update, bevy::window::close_on_esc)
+    if cli::run() {
+        return;
+    }
+
+    let settings = Settings::load();
+    let display = settings.display;
+    let [width, height] = display.resolution;
+
+    let mut app = App::new();
+    if let Some(launch) = cli::launch_song() {
+        app.insert_resource(launch);
+    }
+    app.add_systems(Update, bevy::window::close_on_esc)
         // antialiasing
-        .insert_resource(Msaa::Sample4)
+        .insert_resource(display.msaa())
+        .insert_resource(settings)
         // score tracking
         .insert_resource(Score::new())
         // window configuration
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Drum City!".to_string(),
-                resolution: WindowResolution::new(WINDOW_WIDTH, WINDOW_HEIGHT),
-                present_mode: PresentMode::AutoVsync,
+                resolution: WindowResolution::new(width, height),
+                mode: if display.fullscreen {
+                    WindowMode::BorderlessFullscreen
+                } else {
+                    WindowMode::Windowed
+                },
+                present_mode: display.present_mode(),
                 ..default()
             }),
             ..default()
@@ -63,11 +63,24 @@ fn main() {
         // .insert_resource(State::new(AppState::Menu))
         .add_state::<AppState>()
         .add_plugins(CameraPlugin)
+        .add_plugins(DisplayPlugin)
+        .add_plugins(ChartPlugin)
+        .add_plugins(InputPlugin)
+        .add_plugins(MidiPlugin)
+        .add_plugins(AutoplayPlugin)
+        .add_plugins(ModifiersPlugin)
         .add_plugins(ArrowsPlugin)
+        .add_plugins(GaugePlugin)
+        .add_plugins(PracticePlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(HighScoresPlugin)
+        .add_plugins(ResultsPlugin)
         .add_plugins(UIPlugin)
         .add_plugins(AudioPlugin)
+        .add_plugins(HitSoundsPlugin)
         .add_plugins(ShadersPlugin)
         .add_plugins(MenuPlugin)
+        .add_plugins(OptionsPlugin)
         .add_plugins(DebugPlugin)
         .add_plugins(TimePlugin)
         .add_plugins(MapMakerPlugin)
@@ -75,7 +88,13 @@ fn main() {
 }
 
 fn setup(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+    // always shows at least the area the game is laid out for, whatever the window size
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::AutoMin {
+        min_width: WINDOW_WIDTH,
+        min_height: WINDOW_HEIGHT,
+    };
+    commands.spawn(camera);
 }
 
 pub struct CameraPlugin;
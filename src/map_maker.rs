@@ -1,35 +1,71 @@
-use std::{fs::File, io::Write};
-
+#[cfg(not(target_arch = "wasm32"))]
+use crate::chart::SongIndex;
 use crate::{
-    consts::{AppState, MAP_MAKER_POSITION, START_TIME_OFFSET},
+    analysis::tempo_from_taps,
+    audio::SoundChannel,
+    chart::Chart,
+    consts::{
+        AppState, MAP_MAKER_POSITION, OVERDUB_CONFLICT_WINDOW, SPAWN_POSITION, START_TIME_OFFSET,
+        TAP_TEMPO_RESET, THRESHOLD,
+    },
+    layout::Layout,
+    settings::Settings,
+    storage,
     time::ControlledTime,
     types::*,
 };
-use bevy::prelude::*;
-use serde::Serialize;
+use bevy::{asset::LoadState, audio::Volume, prelude::*};
 
 #[derive(Component)]
 struct MyMusic;
 
-fn setup_audio(mut commands: Commands, audio: Res<MapMakerAudio>) {
+fn setup_audio(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    presses: Res<Presses>,
+) {
+    spawn_music(
+        &mut commands,
+        &asset_server,
+        &settings,
+        &presses.chart.filename,
+    );
+}
+
+fn spawn_music(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    settings: &Settings,
+    filename: &str,
+) {
     commands.spawn((
         AudioBundle {
-            source: audio.0.clone(),
+            source: asset_server.load(format!("songs/{}", filename)),
             settings: PlaybackSettings {
                 paused: true,
+                volume: Volume::new_relative(settings.volume(SoundChannel::Music)),
                 ..default()
             },
         },
         MyMusic,
+        SoundChannel::Music,
     ));
 }
 
-fn start_song(time: Res<ControlledTime>, music_controller: Query<&AudioSink, With<MyMusic>>) {
+/// Starts the music as the game does, `audio_offset` early, so that the chart recorded over it lines
+/// up when it's played
+fn start_song(
+    time: Res<ControlledTime>,
+    settings: Res<Settings>,
+    music_controller: Query<&AudioSink, With<MyMusic>>,
+) {
     let secs = time.elapsed_seconds();
     let secs_last = secs - time.delta_seconds();
 
     // TOOD: without any delay this causes a panic b/c cannot find music_controller (`NoEntities`...)
-    if secs_last <= START_TIME_OFFSET && START_TIME_OFFSET <= secs {
+    let start = START_TIME_OFFSET - settings.audio_offset as f32;
+    if secs_last <= start && start <= secs {
         let sink = music_controller
             .get_single()
             .expect("failed to get audio player");
@@ -37,53 +73,250 @@ fn start_song(time: Res<ControlledTime>, music_controller: Query<&AudioSink, Wit
     }
 }
 
+const SONG_FILE: &str = "akisey-dance.ogg";
+
+/// Chart that the Map Maker records into. The menu picks this before entering `AppState::MakeMap`.
 #[derive(Resource)]
-struct MapMakerAudio(Handle<AudioSource>);
+pub struct MapMakerChart {
+    /// File in `assets/songs` that the chart is written to
+    pub path: String,
+    /// The existing chart at `path`, already loaded, to record on top of instead of starting from
+    /// scratch. See `PendingOverdub`.
+    pub overdub: Option<Handle<Chart>>,
+}
 
-const SONG_FILE: &str = "akisey-dance.ogg";
+impl Default for MapMakerChart {
+    fn default() -> Self {
+        Self {
+            path: "map.toml".to_string(),
+            overdub: None,
+        }
+    }
+}
 
-// The approach here it to create a handle to the material, so that arrows share a reference vs each having their own copy.
-impl FromWorld for MapMakerAudio {
-    fn from_world(world: &mut World) -> Self {
-        let asset_server = world.resource::<AssetServer>();
-        let audio = asset_server.load(format!("songs/{}", SONG_FILE));
-        Self(audio)
+/// A chart to overdub, which the Map Maker opens as soon as it has loaded
+#[derive(Resource)]
+pub struct PendingOverdub {
+    /// Chart file in `assets/songs`
+    pub path: String,
+    pub chart: Handle<Chart>,
+}
+
+impl PendingOverdub {
+    pub fn new(path: &str, asset_server: &AssetServer) -> Self {
+        Self {
+            path: path.to_string(),
+            chart: asset_server.load(format!("songs/{}", path)),
+        }
+    }
+}
+
+fn start_pending_overdub(
+    mut commands: Commands,
+    pending: Res<PendingOverdub>,
+    charts: Res<Assets<Chart>>,
+    asset_server: Res<AssetServer>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if !charts.contains(&pending.chart) {
+        if asset_server.get_load_state(&pending.chart) == Some(LoadState::Failed) {
+            error!("Could not load chart {}", pending.path);
+            commands.remove_resource::<PendingOverdub>();
+        }
+        return;
+    }
+    commands.insert_resource(MapMakerChart {
+        path: pending.path.clone(),
+        overdub: Some(pending.chart.clone()),
+    });
+    commands.remove_resource::<PendingOverdub>();
+    app_state.set(AppState::MakeMap);
+}
+
+/// How a finished pass is combined with the arrows that were already recorded
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OverdubMode {
+    /// Keep existing arrows. A new press replaces an existing arrow in the same lane if they are
+    /// within `OVERDUB_CONFLICT_WINDOW` of each other.
+    Merge,
+    /// Drop existing arrows in the armed lanes for the part of the song that the pass covered
+    Replace,
+}
+
+impl OverdubMode {
+    fn toggled(&self) -> Self {
+        match self {
+            OverdubMode::Merge => OverdubMode::Replace,
+            OverdubMode::Replace => OverdubMode::Merge,
+        }
     }
 }
 
-#[derive(Resource, Serialize, Debug)]
+#[derive(Resource, Debug)]
 struct Presses {
-    arrows: Vec<ArrowTimeToml>,
+    /// Chart being edited. Its `arrows` hold everything from previous passes.
+    chart: SongConfigToml,
+    /// Where the chart is written when the Map Maker exits
+    path: String,
+    /// Arrows recorded during the current pass
+    pass: Vec<ArrowTimeToml>,
+    /// Song time (in seconds) that the current pass has reached
+    recorded_until: f64,
+    pass_number: usize,
+    /// Lanes that the current pass records into
+    armed: Vec<Directions>,
+    mode: OverdubMode,
+    /// Song time range (in seconds) that bulk edits apply to, marked while the song plays
+    selection_start: Option<f64>,
+    selection_end: Option<f64>,
+    /// Song times of the taps of the current tap tempo run
+    taps: Vec<f64>,
     should_save: bool,
+    /// Text of the last save, so that nothing is saved again until the chart changes
+    saved: Option<String>,
+}
+
+impl Presses {
+    /// Merges the current pass into the chart and starts a new, empty pass
+    fn finish_pass(&mut self) {
+        let pass = std::mem::take(&mut self.pass);
+        self.chart.arrows = merge_pass(
+            &self.chart.arrows,
+            &pass,
+            &self.armed,
+            self.mode,
+            self.recorded_until,
+        );
+        self.recorded_until = 0.;
+        self.pass_number += 1;
+    }
+
+    /// The chart as it stands, with the current pass and the tapped tempo
+    fn saved_chart(&self) -> SongConfigToml {
+        let mut chart = self.chart.clone();
+        chart.arrows = merge_pass(
+            &self.chart.arrows,
+            &self.pass,
+            &self.armed,
+            self.mode,
+            self.recorded_until,
+        );
+        if let Some((bpm, offset)) = tempo_from_taps(&self.taps) {
+            chart.bpm = Some(bpm);
+            chart.offset = Some(offset);
+        }
+        chart
+    }
+
+    /// Writes the chart to `assets/songs`. The browser keeps it in local storage instead, and
+    /// offers it as a download to add to the game's assets. Returns `false` if the chart hasn't
+    /// changed since it was last saved.
+    fn save(&mut self) -> bool {
+        let text =
+            toml::to_string(&self.saved_chart()).expect("Couldn't convert presses to toml text");
+        if self.saved.as_ref() == Some(&text) {
+            return false;
+        }
+        storage::write(&format!("assets/songs/{}", self.path), &text);
+        #[cfg(not(target_arch = "wasm32"))]
+        SongIndex::add_chart(&self.path);
+        #[cfg(target_arch = "wasm32")]
+        storage::download(&self.path, &text);
+        self.saved = Some(text);
+        true
+    }
 }
 
 impl Drop for Presses {
     fn drop(&mut self) {
-        if !self.should_save {
-            return;
+        if self.should_save {
+            self.save();
         }
+    }
+}
 
-        let out = SongConfigToml {
+/// Combines the arrows recorded in a pass with the existing arrows of a chart.
+///
+/// Only lanes in `armed` are affected. `recorded_until` is the song time the pass reached, so that
+/// `OverdubMode::Replace` leaves the rest of the song untouched when a pass is cut short.
+pub fn merge_pass(
+    existing: &[ArrowTimeToml],
+    pass: &[ArrowTimeToml],
+    armed: &[Directions],
+    mode: OverdubMode,
+    recorded_until: f64,
+) -> Vec<ArrowTimeToml> {
+    let mut merged: Vec<ArrowTimeToml> = existing
+        .iter()
+        .filter(|arrow| {
+            if !armed.contains(&arrow.direction) {
+                return true;
+            }
+            match mode {
+                OverdubMode::Merge => !pass.iter().any(|new| {
+                    new.direction == arrow.direction
+                        && (new.click_time - arrow.click_time).abs() < OVERDUB_CONFLICT_WINDOW
+                }),
+                OverdubMode::Replace => arrow.click_time > recorded_until,
+            }
+        })
+        .cloned()
+        .collect();
+
+    merged.extend(
+        pass.iter()
+            .filter(|arrow| armed.contains(&arrow.direction))
+            .cloned(),
+    );
+    merged.sort_by(|a, b| a.click_time.partial_cmp(&b.click_time).unwrap());
+    merged
+}
+
+fn setup_key_presses_storage(
+    mut presses: ResMut<Presses>,
+    map_maker_chart: Res<MapMakerChart>,
+    charts: Res<Assets<Chart>>,
+) {
+    let chart = if let Some(overdub) = &map_maker_chart.overdub {
+        // the copy the browser saved, if any, or else the chart's asset
+        match storage::read(&format!("assets/songs/{}", map_maker_chart.path)) {
+            Some(text) => parse_config(&text).expect("Could not parse into SongConfigToml"),
+            None => charts
+                .get(overdub)
+                .expect("Overdubs start once their chart has loaded")
+                .config
+                .clone(),
+        }
+    } else {
+        SongConfigToml {
             name: "Map Maker output".to_string(),
             filename: SONG_FILE.to_string(),
-            arrows: self.arrows.clone(),
-        };
-        let text = toml::to_string(&out).expect("Couldn't convert presses to toml text");
-        let mut file = File::create("assets/songs/map.toml").expect("Couldn't open map.toml file");
-        file.write_all(text.as_bytes())
-            .expect("Couldn't write to map.toml file");
-    }
-}
+            ..Default::default()
+        }
+    };
 
-fn setup_key_presses_storage(mut presses: ResMut<Presses>) {
+    presses.chart = chart;
+    presses.path = map_maker_chart.path.clone();
+    presses.pass.clear();
+    presses.pass_number = 1;
+    presses.selection_start = None;
+    presses.selection_end = None;
+    presses.taps.clear();
     presses.should_save = true;
+    presses.saved = None;
 }
 
 fn save_key_presses(
     time: Res<ControlledTime>,
     keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
     mut presses: ResMut<Presses>,
 ) {
+    presses.recorded_until = time.elapsed_seconds_f64() - START_TIME_OFFSET as f64;
+    // presses are late by the input offset, as in the game
+    let click_time = presses.recorded_until - settings.input_offset;
+    let speed = recording_speed(&keyboard_input);
+
     let directions = [
         Directions::Up,
         Directions::Down,
@@ -91,40 +324,209 @@ fn save_key_presses(
         Directions::Right,
     ];
     for direction in directions.iter() {
-        if direction.key_just_pressed(&keyboard_input) {
-            presses.arrows.push(ArrowTimeToml {
-                click_time: time.elapsed_seconds_f64() - START_TIME_OFFSET as f64,
-                speed: Speed::Slow,
+        if settings
+            .key_bindings
+            .just_pressed(*direction, &keyboard_input)
+            && presses.armed.contains(direction)
+        {
+            presses.pass.push(ArrowTimeToml {
+                click_time,
+                speed,
                 direction: *direction,
+                keysound: None,
             });
         }
     }
 }
 
-// fn save_to_file_on_exit(mut event_reader: EventReader<AppExit>, presses: Res<Presses>) {
-//     for event in event_reader.read() {
-//         info!("event detected: {:?}", event);
-//         let text = toml::to_string(&*presses).expect("Couldn't convert presses to toml text");
-//         let mut file = File::create("assets/songs/map.toml").expect("Couldn't open map.toml file");
-//         file.write_all(text.as_bytes())
-//             .expect("Couldn't write to map.toml file");
-//     }
-// }
+/// Speed given to recorded arrows: Slow by default, Medium while Shift is held, Fast while Ctrl is held.
+/// Ctrl is ignored while it's held to save with Ctrl+S.
+pub fn recording_speed(keyboard_input: &Input<KeyCode>) -> Speed {
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && !keyboard_input.pressed(KeyCode::S)
+    {
+        Speed::Fast
+    } else if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        Speed::Medium
+    } else {
+        Speed::Slow
+    }
+}
+
+/// Sets the speed of every arrow in the armed lanes whose click time falls within `start..=end`
+pub fn set_speed_in_range(
+    arrows: &mut [ArrowTimeToml],
+    start: f64,
+    end: f64,
+    lanes: &[Directions],
+    speed: Speed,
+) {
+    for arrow in arrows.iter_mut() {
+        if (start..=end).contains(&arrow.click_time) && lanes.contains(&arrow.direction) {
+            arrow.speed = speed;
+        }
+    }
+}
+
+/// `[` and `]` mark the start and end of a selection at the current song time.
+/// Z, X and C then set the arrows of the armed lanes in the selection to Slow, Medium and Fast.
+fn edit_selection(
+    keyboard_input: Res<Input<KeyCode>>,
+    materials: Res<MapMakerArrowMaterialResource>,
+    mut presses: ResMut<Presses>,
+    mut playback_arrows: Query<(&mut PlaybackArrow, &mut Handle<Image>)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        presses.selection_start = Some(presses.recorded_until);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        presses.selection_end = Some(presses.recorded_until);
+    }
+
+    let speed = if keyboard_input.just_pressed(KeyCode::Z) {
+        Speed::Slow
+    } else if keyboard_input.just_pressed(KeyCode::X) {
+        Speed::Medium
+    } else if keyboard_input.just_pressed(KeyCode::C) {
+        Speed::Fast
+    } else {
+        return;
+    };
+
+    let start = presses.selection_start.unwrap_or(f64::NEG_INFINITY);
+    let end = presses.selection_end.unwrap_or(f64::INFINITY);
+    let presses = presses.as_mut();
+    set_speed_in_range(&mut presses.chart.arrows, start, end, &presses.armed, speed);
+    set_speed_in_range(&mut presses.pass, start, end, &presses.armed, speed);
+
+    for (mut arrow, mut texture) in playback_arrows.iter_mut() {
+        set_speed_in_range(
+            std::slice::from_mut(&mut arrow.0),
+            start,
+            end,
+            &presses.armed,
+            speed,
+        );
+        *texture = materials.speed_image(arrow.0.speed);
+    }
+}
+
+/// T taps along with the beat of the music, to estimate the song's BPM and first beat offset.
+/// Pausing for a while starts a new run of taps. Taps are late by the input offset, like presses.
+fn tap_tempo(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut presses: ResMut<Presses>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::T) {
+        return;
+    }
+
+    let now = presses.recorded_until - settings.input_offset;
+    if let Some(last) = presses.taps.last() {
+        if now - last > TAP_TEMPO_RESET {
+            presses.taps.clear();
+        }
+    }
+    presses.taps.push(now);
+}
+
+/// Number keys 1-4 arm or disarm the Up, Down, Left and Right lanes for the current pass.
+/// Tab switches the overdub mode.
+fn arm_lanes(keyboard_input: Res<Input<KeyCode>>, mut presses: ResMut<Presses>) {
+    let lanes = [
+        (KeyCode::Key1, Directions::Up),
+        (KeyCode::Key2, Directions::Down),
+        (KeyCode::Key3, Directions::Left),
+        (KeyCode::Key4, Directions::Right),
+    ];
+    for (key, direction) in lanes.iter() {
+        if keyboard_input.just_pressed(*key) {
+            if let Some(index) = presses.armed.iter().position(|d| d == direction) {
+                presses.armed.remove(index);
+            } else {
+                presses.armed.push(*direction);
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        presses.mode = presses.mode.toggled();
+    }
+}
+
+/// Ctrl+S saves the chart without leaving the Map Maker, which the browser never does
+fn save_chart(keyboard_input: Res<Input<KeyCode>>, mut presses: ResMut<Presses>) {
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && keyboard_input.just_pressed(KeyCode::S)
+        && presses.save()
+    {
+        info!("Saved {}", presses.path);
+    }
+}
+
+/// Enter finishes the current pass and starts the song over for the next one
+#[allow(clippy::too_many_arguments)]
+fn next_pass(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut presses: ResMut<Presses>,
+    mut time: ResMut<ControlledTime>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    materials: Res<MapMakerArrowMaterialResource>,
+    music: Query<Entity, With<MyMusic>>,
+    playback_arrows: Query<Entity, With<PlaybackArrow>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    presses.finish_pass();
+    time.reset_time();
+
+    for entity in music.iter().chain(playback_arrows.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_music(
+        &mut commands,
+        &asset_server,
+        &settings,
+        &presses.chart.filename,
+    );
+    spawn_playback_arrows(&mut commands, &materials, &presses.chart.arrows);
+}
 
 /// Keep textures for the arrow
 #[derive(Resource)]
 struct MapMakerArrowMaterialResource {
+    red_image: Handle<Image>,
+    blue_image: Handle<Image>,
+    green_image: Handle<Image>,
     border_image: Handle<Image>,
 }
 
+impl MapMakerArrowMaterialResource {
+    /// Returns the arrow image that matches the color used in game for this speed
+    fn speed_image(&self, speed: Speed) -> Handle<Image> {
+        match speed {
+            Speed::Slow => self.green_image.clone(),
+            Speed::Medium => self.blue_image.clone(),
+            Speed::Fast => self.red_image.clone(),
+        }
+    }
+}
+
 // The approach here it to create a handle to the material, so that arrows share a reference vs each having their own copy.
 impl FromWorld for MapMakerArrowMaterialResource {
     fn from_world(world: &mut World) -> Self {
         let asset_server = world.resource::<AssetServer>();
-        let border_handle = asset_server.load("images/arrow_border.png");
 
         MapMakerArrowMaterialResource {
-            border_image: border_handle,
+            red_image: asset_server.load("images/arrow_red.png"),
+            blue_image: asset_server.load("images/arrow_blue.png"),
+            green_image: asset_server.load("images/arrow_green.png"),
+            border_image: asset_server.load("images/arrow_border.png"),
         }
     }
 }
@@ -139,8 +541,9 @@ fn setup_map_maker_arrows(mut commands: Commands, materials: Res<MapMakerArrowMa
         Directions::Left,
         Directions::Right,
     ] {
-        let mut transform =
-            Transform::from_translation(Vec3::new(MAP_MAKER_POSITION, direction.y(), 1.));
+        // the map maker always scrolls horizontally
+        let y = Layout::horizontal().lane_position(*direction);
+        let mut transform = Transform::from_translation(Vec3::new(MAP_MAKER_POSITION, y, 1.));
         transform.rotate(Quat::from_rotation_z(direction.rotation()));
 
         commands
@@ -160,9 +563,10 @@ fn setup_map_maker_arrows(mut commands: Commands, materials: Res<MapMakerArrowMa
 fn toggle_map_maker_arrows(
     mut query: Query<(&MapMakerArrow, &mut Visibility)>,
     input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
 ) {
     for (arrow, mut visible) in query.iter_mut() {
-        if arrow.0.key_pressed(&input) {
+        if settings.key_bindings.pressed(arrow.0, &input) {
             *visible = Visibility::Visible;
         } else {
             *visible = Visibility::Hidden;
@@ -170,27 +574,197 @@ fn toggle_map_maker_arrows(
     }
 }
 
+/// An arrow from an earlier pass, played back while recording the next one
+#[derive(Component)]
+struct PlaybackArrow(ArrowTimeToml);
+
+fn spawn_playback_arrows(
+    commands: &mut Commands,
+    materials: &MapMakerArrowMaterialResource,
+    arrows: &[ArrowTimeToml],
+) {
+    for arrow in arrows {
+        let texture = materials.speed_image(arrow.speed);
+
+        let y = Layout::horizontal().lane_position(arrow.direction);
+        let mut transform = Transform::from_translation(Vec3::new(SPAWN_POSITION, y, 1.));
+        transform.rotate(Quat::from_rotation_z(arrow.direction.rotation()));
+
+        commands.spawn((
+            SpriteBundle {
+                texture,
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(140., 140.)),
+                    ..Default::default()
+                },
+                transform,
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            PlaybackArrow(arrow.clone()),
+        ));
+    }
+}
+
+fn setup_playback_arrows(
+    mut commands: Commands,
+    materials: Res<MapMakerArrowMaterialResource>,
+    presses: Res<Presses>,
+) {
+    spawn_playback_arrows(&mut commands, &materials, &presses.chart.arrows);
+}
+
+/// Places arrows from earlier passes so that they cross the Map Maker arrows at their click time
+fn move_playback_arrows(
+    time: Res<ControlledTime>,
+    mut query: Query<(&PlaybackArrow, &mut Transform, &mut Visibility)>,
+) {
+    let secs = time.elapsed_seconds_f64() - START_TIME_OFFSET as f64;
+
+    for (arrow, mut transform, mut visibility) in query.iter_mut() {
+        let x = MAP_MAKER_POSITION - ((arrow.0.click_time - secs) as f32) * arrow.0.speed.value();
+        transform.translation.x = x;
+
+        *visibility = if (SPAWN_POSITION..=MAP_MAKER_POSITION + THRESHOLD).contains(&x) {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+#[derive(Component)]
+struct MapMakerStatusText;
+
+fn setup_status_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.),
+                top: Val::Px(10.),
+                ..Default::default()
+            },
+            background_color: BackgroundColor(Color::NONE),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: String::new(),
+                            style: TextStyle {
+                                font: font.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(MapMakerStatusText);
+        });
+}
+
+fn update_status_text(
+    presses: Res<Presses>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<&mut Text, With<MapMakerStatusText>>,
+) {
+    let armed = presses
+        .armed
+        .iter()
+        .map(|direction| format!("{:?}", direction))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let tempo = match tempo_from_taps(&presses.taps) {
+        Some((bpm, offset)) => format!("{:.1} BPM, first beat at {:.2}s", bpm, offset),
+        None => "tap T on the beat".to_string(),
+    };
+    let selection_bound =
+        |bound: Option<f64>| bound.map_or("-".to_string(), |secs| format!("{:.2}", secs));
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!(
+            "Pass {} ({:?}). Recording: {} ({:?})\nSelection: {} to {}\nTempo: {}\n\
+            1-4: arm lanes. Tab: mode. Enter: next pass. T: tap tempo. Ctrl+S: save\n\
+            Shift/Ctrl: record Medium/Fast. [ ]: select. Z/X/C: set Slow/Medium/Fast",
+            presses.pass_number,
+            presses.mode,
+            armed,
+            recording_speed(&keyboard_input),
+            selection_bound(presses.selection_start),
+            selection_bound(presses.selection_end),
+            tempo,
+        );
+    }
+}
+
 pub struct MapMakerPlugin;
 impl Plugin for MapMakerPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Presses {
-            arrows: Vec::new(),
+            chart: SongConfigToml::default(),
+            path: String::new(),
+            pass: Vec::new(),
+            recorded_until: 0.,
+            pass_number: 1,
+            armed: vec![
+                Directions::Up,
+                Directions::Down,
+                Directions::Left,
+                Directions::Right,
+            ],
+            mode: OverdubMode::Merge,
+            selection_start: None,
+            selection_end: None,
+            taps: Vec::new(),
             should_save: false,
+            saved: None,
         })
+        .init_resource::<MapMakerChart>()
+        .add_systems(
+            Update,
+            start_pending_overdub
+                .run_if(in_state(AppState::Menu))
+                .run_if(resource_exists::<PendingOverdub>()),
+        )
         .init_resource::<MapMakerArrowMaterialResource>()
-        .init_resource::<MapMakerAudio>()
-        .add_systems(OnEnter(AppState::MakeMap), setup_audio)
-        .add_systems(OnEnter(AppState::MakeMap), setup_key_presses_storage)
+        .add_systems(
+            OnEnter(AppState::MakeMap),
+            (
+                setup_key_presses_storage,
+                setup_audio,
+                setup_playback_arrows,
+                setup_status_text,
+            )
+                .chain(),
+        )
         // TODO: ideally this would run once on startup, but I can't figure out how to do that safely
         // with initializing the AudioSink. I tried flushing commands via `apply_deferred`
         // Hitting:
         //  failed to get audio player: NoEntities("bevy_ecs::query::state::QueryState<&bevy_audio::sinks::AudioSink, bevy_ecs::query::filter::With<drum_city::map_maker::MyMusic>>")
         .add_systems(Update, start_song.run_if(in_state(AppState::MakeMap)))
-        .add_systems(Update, save_key_presses.run_if(in_state(AppState::MakeMap)))
-        // .add_systems(
-        //     Update,
-        //     save_to_file_on_exit.run_if(in_state(AppState::MakeMap)),
-        // )
+        .add_systems(
+            Update,
+            (
+                arm_lanes,
+                save_key_presses,
+                tap_tempo,
+                edit_selection,
+                next_pass,
+                save_chart,
+            )
+                .chain()
+                .run_if(in_state(AppState::MakeMap)),
+        )
+        .add_systems(
+            Update,
+            (move_playback_arrows, update_status_text).run_if(in_state(AppState::MakeMap)),
+        )
         .add_systems(OnEnter(AppState::MakeMap), setup_map_maker_arrows)
         .add_systems(
             Update,
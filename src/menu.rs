@@ -1,39 +1,169 @@
-use std::fs::read_dir;
-
 use bevy::prelude::*;
 
-use crate::{consts::AppState, types::load_config};
+use crate::{
+    arrows::StartPosition,
+    audio::{play_sound, SoundChannel},
+    autoplay::Autoplay,
+    chart::{Chart, PendingSong, SongIndex, SONG_INDEX},
+    cli::LaunchSong,
+    consts::{AppState, ATTRACT_MODE_DELAY},
+    high_scores::HighScores,
+    map_maker::{MapMakerChart, PendingOverdub},
+    modifiers::Modifiers,
+    replay::{Replay, ReplayPlayback},
+    settings::Settings,
+    time::unix_time,
+};
 
 /// Keep textures and materials for arrows
 #[derive(Resource)]
 struct ButtonMaterials {
     font: Handle<Font>,
+    click: Handle<AudioSource>,
 }
 
-const NORMAL_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const NORMAL_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
 const PRESSED_COLOR: Color = Color::rgb(0.35, 0.75, 0.35);
-const FONT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+pub const FONT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
 
 impl FromWorld for ButtonMaterials {
     fn from_world(world: &mut World) -> Self {
         let asset_server = world.resource::<AssetServer>();
         let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        let click = asset_server.load("sounds/click.wav");
 
         // let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
-        ButtonMaterials { font }
+        ButtonMaterials { font, click }
+    }
+}
+
+/// Songs on the menu, from the song index. Their charts stay loaded, for their personal bests.
+#[derive(Resource)]
+pub struct Songs {
+    index: Handle<SongIndex>,
+    /// Chart file in `assets/songs` and chart of each song, in menu order
+    charts: Vec<(String, Handle<Chart>)>,
+}
+
+impl FromWorld for Songs {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Songs {
+            index: asset_server.load(SONG_INDEX),
+            charts: Vec::new(),
+        }
+    }
+}
+
+impl Songs {
+    /// Names of the songs, which are their chart files without the extension
+    pub fn names(&self) -> Vec<String> {
+        self.charts
+            .iter()
+            .map(|(file, _)| file.trim_end_matches(".toml").to_string())
+            .collect()
+    }
+}
+
+/// Loads the charts of the song index, whenever it loads or changes
+fn load_listed_charts(
+    mut events: EventReader<AssetEvent<SongIndex>>,
+    indexes: Res<Assets<SongIndex>>,
+    asset_server: Res<AssetServer>,
+    mut songs: ResMut<Songs>,
+) {
+    let changed = events.read().any(|event| {
+        event.is_loaded_with_dependencies(&songs.index) || event.is_modified(&songs.index)
+    });
+    let Some(index) = indexes.get(&songs.index).filter(|_| changed) else {
+        return;
+    };
+    songs.charts = index
+        .charts
+        .iter()
+        .map(|chart| (chart.clone(), asset_server.load(format!("songs/{}", chart))))
+        .collect();
+}
+
+/// The song list or one of its charts has loaded or changed since the menu was laid out
+fn songs_changed(
+    mut index_events: EventReader<AssetEvent<SongIndex>>,
+    mut chart_events: EventReader<AssetEvent<Chart>>,
+) -> bool {
+    // both are read to the end, so that old events don't count next time
+    let index_changed = index_events.read().count() > 0;
+    let chart_changed = chart_events.read().count() > 0;
+    index_changed || chart_changed
+}
+
+/// Chart positions that songs can be started from with the "Start" button, in seconds
+const START_POSITIONS: [f64; 7] = [0., 15., 30., 45., 60., 90., 120.];
+
+/// Where "Play song" starts the song, picked with the "Start" button. Songs started part way
+/// through don't count towards high scores, see `StartPosition`.
+#[derive(Resource, Default)]
+pub struct MenuStart(pub f64);
+
+impl MenuStart {
+    /// Goes to the next start position, wrapping around to the beginning of the song
+    fn next(&mut self) {
+        self.0 = START_POSITIONS
+            .iter()
+            .copied()
+            .find(|start| *start > self.0)
+            .unwrap_or(START_POSITIONS[0]);
     }
 }
 
 #[derive(Component)]
 struct MenuUI;
 
-fn setup_menu(mut commands: Commands, button_materials: Res<ButtonMaterials>) {
-    let mut buttons: Vec<MenuButton> = get_songs()
+fn setup_menu(
+    mut commands: Commands,
+    button_materials: Res<ButtonMaterials>,
+    high_scores: Res<HighScores>,
+    modifiers: Res<Modifiers>,
+    start: Res<MenuStart>,
+    songs: Res<Songs>,
+    charts: Res<Assets<Chart>>,
+) {
+    // one row per song, with the actions available for it and its personal best
+    let mut rows: Vec<(Vec<MenuButton>, Option<String>)> = songs
+        .charts
         .iter()
-        .map(|name| MenuButton::PlaySong(name.clone()))
+        .map(|(file, chart)| {
+            let name = file.trim_end_matches(".toml").to_string();
+            // charts that haven't loaded yet show theirs once they have
+            let best = charts
+                .get(chart)
+                .and_then(|chart| high_scores.personal_best(&chart.hash))
+                .map(|best| format!("Best: {}", best.score));
+
+            let mut row = vec![
+                MenuButton::PlaySong(name.clone()),
+                MenuButton::Preview(name.clone()),
+                MenuButton::Practice(name.clone()),
+                MenuButton::OverdubMap(name.clone()),
+            ];
+            if Replay::exists(&format!("{}.toml", name)) {
+                row.push(MenuButton::WatchReplay(name));
+            }
+            (row, best)
+        })
         .collect();
-    buttons.push(MenuButton::MakeMap);
+    rows.push((
+        vec![
+            MenuButton::Rate,
+            MenuButton::Mirror,
+            MenuButton::Shuffle,
+            MenuButton::Hidden,
+            MenuButton::Sudden,
+            MenuButton::Start,
+        ],
+        None,
+    ));
+    rows.push((vec![MenuButton::MakeMap, MenuButton::Options], None));
 
     commands
         .spawn((
@@ -52,51 +182,98 @@ fn setup_menu(mut commands: Commands, button_materials: Res<ButtonMaterials>) {
             MenuUI,
         ))
         .with_children(|parent| {
-            for button in buttons {
-                let name = button.name();
+            for (row, best) in rows {
                 parent
-                    .spawn((
-                        ButtonBundle {
-                            style: Style {
-                                width: Val::Px(350.0),
-                                height: Val::Px(65.0),
-                                margin: UiRect::all(Val::Auto),
-                                justify_content: JustifyContent::Center,
-                                align_items: AlignItems::Center,
-                                ..default()
-                            },
-                            background_color: BackgroundColor(NORMAL_COLOR),
+                    .spawn(NodeBundle {
+                        style: Style {
+                            margin: UiRect::all(Val::Auto),
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(10.),
                             ..default()
                         },
-                        button,
-                    ))
+                        ..default()
+                    })
                     .with_children(|parent| {
-                        parent.spawn(TextBundle {
-                            text: Text {
-                                sections: vec![TextSection::new(
-                                    name,
-                                    TextStyle {
-                                        font_size: 20.0,
-                                        color: FONT_COLOR,
-                                        font: button_materials.font.clone(),
-                                        ..default()
-                                    },
-                                )],
-                                ..default()
-                            },
-                            ..default()
-                        });
+                        for button in row {
+                            spawn_button(parent, button, &button_materials, &modifiers, &start);
+                        }
+                        if let Some(best) = best {
+                            parent.spawn(TextBundle::from_section(
+                                best,
+                                TextStyle {
+                                    font_size: 20.0,
+                                    color: FONT_COLOR,
+                                    font: button_materials.font.clone(),
+                                },
+                            ));
+                        }
                     });
             }
         });
 }
 
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: MenuButton,
+    button_materials: &ButtonMaterials,
+    modifiers: &Modifiers,
+    start: &MenuStart,
+) {
+    let name = button.name(modifiers, start);
+    let width = match button {
+        MenuButton::MakeMap => 350.0,
+        MenuButton::PlaySong(_) => 250.0,
+        MenuButton::Rate
+        | MenuButton::Mirror
+        | MenuButton::Shuffle
+        | MenuButton::Hidden
+        | MenuButton::Sudden
+        | MenuButton::Start
+        | MenuButton::Options => 130.0,
+        _ => 100.0,
+    };
+
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(65.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(NORMAL_COLOR),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle {
+                text: Text {
+                    sections: vec![TextSection::new(
+                        name,
+                        TextStyle {
+                            font_size: 20.0,
+                            color: FONT_COLOR,
+                            font: button_materials.font.clone(),
+                        },
+                    )],
+                    ..default()
+                },
+                ..default()
+            });
+        });
+}
+
 fn despawn_menu(mut commands: Commands, query: Query<(Entity, &MenuUI)>) {
     for (entity, _) in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn update_button_color(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
@@ -114,60 +291,136 @@ fn update_button_color(
     }
 }
 
-pub fn get_songs() -> Vec<String> {
-    let paths = read_dir("assets/songs").unwrap();
-
-    let mut vec = vec![];
-    for dir_entry in paths {
-        let path = dir_entry.unwrap().path();
-
-        let file_extension = path.as_path().extension().unwrap();
-        if file_extension == "toml" {
-            let name_without_extension = path
-                .as_path()
-                .file_stem()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            vec.push(name_without_extension);
+fn play_button_sounds(
+    mut commands: Commands,
+    button_materials: Res<ButtonMaterials>,
+    settings: Res<Settings>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            play_sound(
+                &mut commands,
+                &settings,
+                button_materials.click.clone(),
+                SoundChannel::Ui,
+            );
         }
     }
-    vec
 }
 
 #[derive(Component, Debug)]
 pub enum MenuButton {
     MakeMap,
+    OverdubMap(String),
     PlaySong(String),
+    Practice(String),
+    Preview(String),
+    WatchReplay(String),
+    Rate,
+    Mirror,
+    Shuffle,
+    Hidden,
+    Sudden,
+    Start,
+    Options,
 }
 
 impl MenuButton {
-    fn name(&self) -> String {
+    fn name(&self, modifiers: &Modifiers, start: &MenuStart) -> String {
+        let toggle =
+            |name: &str, enabled: bool| format!("{}: {}", name, if enabled { "on" } else { "off" });
         match self {
             MenuButton::MakeMap => "Make Map".to_string(),
+            MenuButton::OverdubMap(_) => "Overdub".to_string(),
+            MenuButton::WatchReplay(_) => "Replay".to_string(),
+            MenuButton::Preview(_) => "Preview".to_string(),
+            MenuButton::Practice(_) => "Practice".to_string(),
             MenuButton::PlaySong(name) => format!("Play song: {}", name),
+            MenuButton::Rate => format!("Rate: {}x", modifiers.rate),
+            MenuButton::Mirror => toggle("Mirror", modifiers.mirror),
+            MenuButton::Shuffle => toggle("Shuffle", modifiers.shuffle),
+            MenuButton::Hidden => toggle("Hidden", modifiers.hidden),
+            MenuButton::Sudden => toggle("Sudden", modifiers.sudden),
+            MenuButton::Start => format!("Start: {}s", start.0),
+            MenuButton::Options => "Options".to_string(),
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn button_press_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     interaction_query: Query<(&Interaction, &MenuButton), (Changed<Interaction>, With<Button>)>,
     mut app_state: ResMut<NextState<AppState>>,
+    mut autoplay: ResMut<Autoplay>,
+    mut modifiers: ResMut<Modifiers>,
+    mut start: ResMut<MenuStart>,
 ) {
     for (interaction, button) in interaction_query.iter() {
         if *interaction == Interaction::Pressed {
             match button {
                 MenuButton::MakeMap => {
+                    commands.insert_resource(MapMakerChart::default());
                     app_state.set(AppState::MakeMap);
                     return;
                 }
+                MenuButton::OverdubMap(song) => {
+                    commands.insert_resource(PendingOverdub::new(
+                        &format!("{}.toml", song),
+                        &asset_server,
+                    ));
+                    return;
+                }
+                MenuButton::WatchReplay(song) => {
+                    let path = format!("{}.toml", song);
+                    let replay = match Replay::load(&path) {
+                        Ok(replay) => replay,
+                        Err(error) => {
+                            warn!("Can't watch the replay: {}", error);
+                            return;
+                        }
+                    };
+                    commands.insert_resource(PendingSong::new(&path, &asset_server));
+                    commands.insert_resource(ReplayPlayback::new(replay, &mut modifiers));
+                    return;
+                }
+                MenuButton::Practice(song) => {
+                    commands.insert_resource(PendingSong {
+                        practice: true,
+                        ..PendingSong::new(&format!("{}.toml", song), &asset_server)
+                    });
+                    return;
+                }
+                MenuButton::Preview(song) => {
+                    commands.insert_resource(PendingSong::new(
+                        &format!("{}.toml", song),
+                        &asset_server,
+                    ));
+                    autoplay.enabled = true;
+                    return;
+                }
                 MenuButton::PlaySong(song) => {
-                    let config = load_config(format!("{}.toml", song).as_str(), &asset_server);
-                    commands.insert_resource(config);
-                    app_state.set(AppState::Game);
+                    commands.insert_resource(PendingSong::new(
+                        &format!("{}.toml", song),
+                        &asset_server,
+                    ));
+                    if start.0 > 0. {
+                        commands.insert_resource(StartPosition(start.0));
+                    }
+                    // a new shuffle every time
+                    modifiers.seed = (unix_time().as_nanos() as u64).max(1);
+                    return;
+                }
+                MenuButton::Rate => modifiers.next_rate(),
+                MenuButton::Mirror => modifiers.mirror = !modifiers.mirror,
+                MenuButton::Shuffle => modifiers.shuffle = !modifiers.shuffle,
+                MenuButton::Hidden => modifiers.hidden = !modifiers.hidden,
+                MenuButton::Sudden => modifiers.sudden = !modifiers.sudden,
+                MenuButton::Start => start.next(),
+                MenuButton::Options => {
+                    app_state.set(AppState::Options);
                     return;
                 }
             }
@@ -175,14 +428,119 @@ pub fn button_press_system(
     }
 }
 
+/// Plays the song given on the command line right away
+fn launch_song(mut commands: Commands, asset_server: Res<AssetServer>, launch: Res<LaunchSong>) {
+    commands.insert_resource(PendingSong::new(&launch.chart, &asset_server));
+    if let Some(start) = launch.start {
+        commands.insert_resource(StartPosition(start));
+    }
+    commands.remove_resource::<LaunchSong>();
+}
+
+/// Starts an autoplay demo of a song when the menu has been left alone for a while
+#[derive(Resource)]
+struct AttractMode {
+    idle: Timer,
+    next_song: usize,
+}
+
+impl Default for AttractMode {
+    fn default() -> Self {
+        Self {
+            idle: Timer::from_seconds(ATTRACT_MODE_DELAY, TimerMode::Once),
+            next_song: 0,
+        }
+    }
+}
+
+fn reset_attract_mode(mut attract_mode: ResMut<AttractMode>) {
+    attract_mode.idle.reset();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn attract_mode(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut attract_mode: ResMut<AttractMode>,
+    mut autoplay: ResMut<Autoplay>,
+    songs: Res<Songs>,
+) {
+    let touched = keyboard_input.get_pressed().next().is_some()
+        || mouse_input.get_pressed().next().is_some()
+        || cursor_moved.read().next().is_some();
+    if touched {
+        attract_mode.idle.reset();
+        return;
+    }
+
+    if !attract_mode.idle.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let songs = songs.names();
+    if songs.is_empty() {
+        return;
+    }
+    let song = &songs[attract_mode.next_song % songs.len()];
+    attract_mode.next_song += 1;
+
+    commands.insert_resource(PendingSong::new(&format!("{}.toml", song), &asset_server));
+    autoplay.enabled = true;
+    autoplay.demo = true;
+}
+
+/// Shows the modifiers' and start position's new values on their buttons
+fn update_modifier_labels(
+    modifiers: Res<Modifiers>,
+    start: Res<MenuStart>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !modifiers.is_changed() && !start.is_changed() {
+        return;
+    }
+    for (button, children) in buttons.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = button.name(&modifiers, &start);
+            }
+        }
+    }
+}
+
 pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ButtonMaterials>()
-            .add_systems(OnEnter(AppState::Menu), setup_menu)
+            .init_resource::<AttractMode>()
+            .init_resource::<MenuStart>()
+            .init_resource::<Songs>()
+            .add_systems(Update, load_listed_charts)
+            .add_systems(
+                Update,
+                (despawn_menu, setup_menu)
+                    .chain()
+                    .after(load_listed_charts)
+                    .run_if(in_state(AppState::Menu))
+                    .run_if(songs_changed),
+            )
+            .add_systems(
+                Update,
+                launch_song
+                    .run_if(in_state(AppState::Menu))
+                    .run_if(resource_exists::<LaunchSong>()),
+            )
+            .add_systems(OnEnter(AppState::Menu), (setup_menu, reset_attract_mode))
+            // the options screen has buttons too
+            .add_systems(Update, (update_button_color, play_button_sounds))
             .add_systems(
                 Update,
-                (update_button_color, button_press_system).run_if(in_state(AppState::Menu)),
+                (button_press_system, update_modifier_labels, attract_mode)
+                    .run_if(in_state(AppState::Menu)),
             )
             .add_systems(OnExit(AppState::Menu), despawn_menu);
     }
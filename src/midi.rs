@@ -0,0 +1,267 @@
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+
+use bevy::prelude::*;
+use midir::MidiInputConnection;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    autoplay::autoplay_enabled,
+    consts::AppState,
+    input::{LaneInputSet, LanePressed},
+    replay::ReplayPlayback,
+    settings::Settings,
+    time::ControlledTime,
+    types::{Directions, SongConfig},
+};
+
+/// A note played on a MIDI instrument, e.g. a pad of a drum kit
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub struct MidiNoteOn {
+    pub note: u8,
+    /// How hard the note was played, from 1 to 127
+    pub velocity: u8,
+}
+
+impl MidiNoteOn {
+    /// Reads a note-on message, on any channel. Other messages, and note-ons with a velocity of
+    /// 0 which mean note-off, are ignored.
+    pub fn parse(message: &[u8]) -> Option<MidiNoteOn> {
+        match *message {
+            [status, note, velocity, ..] if status & 0xF0 == 0x90 && velocity > 0 => {
+                Some(MidiNoteOn { note, velocity })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A note and the lane it plays
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct NoteBinding {
+    pub note: u8,
+    pub lane: Directions,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MidiSettings {
+    /// Listens to a MIDI input when the game starts
+    pub enabled: bool,
+    /// Part of the name of the MIDI input to listen to. The first one is used otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    /// Lane of each note. Notes that aren't listed are ignored.
+    pub notes: Vec<NoteBinding>,
+}
+
+/// Lanes of the General MIDI percussion notes, which drum kits send and drum tracks use
+pub const GENERAL_MIDI_DRUMS: [(u8, Directions); 19] = [
+    (35, Directions::Kick),
+    (36, Directions::Kick),
+    (37, Directions::Snare),
+    (38, Directions::Snare),
+    (40, Directions::Snare),
+    (42, Directions::HiHat),
+    (44, Directions::HiHat),
+    (46, Directions::HiHat),
+    (41, Directions::Tom),
+    (43, Directions::Tom),
+    (45, Directions::Tom),
+    (47, Directions::Tom),
+    (48, Directions::Tom),
+    (50, Directions::Tom),
+    (49, Directions::Crash),
+    (51, Directions::Crash),
+    (52, Directions::Crash),
+    (55, Directions::Crash),
+    (57, Directions::Crash),
+];
+
+impl Default for MidiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: None,
+            notes: GENERAL_MIDI_DRUMS
+                .iter()
+                .map(|&(note, lane)| NoteBinding { note, lane })
+                .collect(),
+        }
+    }
+}
+
+impl MidiSettings {
+    /// Lane that a note plays, if any
+    pub fn lane(&self, note: u8) -> Option<Directions> {
+        self.notes
+            .iter()
+            .find(|binding| binding.note == note)
+            .map(|binding| binding.lane)
+    }
+}
+
+/// Raw MIDI messages, from a device or from a `MidiLoopback`
+#[derive(Resource)]
+pub struct MidiInput {
+    messages: Mutex<Receiver<Vec<u8>>>,
+}
+
+impl MidiInput {
+    /// An input that only receives what is sent to the returned loopback, for tests and for
+    /// when there is no MIDI device
+    pub fn loopback() -> (MidiInput, MidiLoopback) {
+        let (sender, receiver) = channel();
+        let input = MidiInput {
+            messages: Mutex::new(receiver),
+        };
+        (input, MidiLoopback(sender))
+    }
+}
+
+/// Sends messages to a `MidiInput` as if a device had sent them
+#[derive(Resource, Clone)]
+pub struct MidiLoopback(Sender<Vec<u8>>);
+
+impl MidiLoopback {
+    pub fn send(&self, message: &[u8]) {
+        // nothing to do if the game is gone
+        let _ = self.0.send(message.to_vec());
+    }
+
+    pub fn note_on(&self, note: u8, velocity: u8) {
+        self.send(&[0x90, note, velocity]);
+    }
+}
+
+/// Keeps the MIDI device open. The connection can't move between threads, so it's a non-send
+/// resource.
+struct MidiConnection {
+    _connection: MidiInputConnection<()>,
+}
+
+/// Where a MIDI device, once one is opened, sends its messages
+#[derive(Resource)]
+struct MidiDevice(MidiLoopback);
+
+/// Sets up an input for a MIDI device. Until one is opened, a loopback that nothing sends to
+/// stands in for it.
+fn setup_midi_input(mut commands: Commands) {
+    let (input, loopback) = MidiInput::loopback();
+    commands.insert_resource(input);
+    commands.insert_resource(MidiDevice(loopback));
+}
+
+/// Opens the MIDI device picked in the settings while MIDI input is on, and closes it while it's
+/// off
+fn connect_midi(world: &mut World) {
+    let settings = world.resource::<Settings>().midi.clone();
+    let connected = world.contains_non_send::<MidiConnection>();
+    if settings.enabled == connected {
+        return;
+    }
+    if !settings.enabled {
+        world.remove_non_send_resource::<MidiConnection>();
+        info!("Stopped listening to MIDI input");
+        return;
+    }
+    let loopback = world.resource::<MidiDevice>().0.clone();
+    match open_port(&settings, loopback) {
+        Ok(connection) => world.insert_non_send_resource(MidiConnection {
+            _connection: connection,
+        }),
+        Err(error) => warn!("No MIDI input: {}", error),
+    }
+}
+
+fn open_port(
+    settings: &MidiSettings,
+    loopback: MidiLoopback,
+) -> Result<MidiInputConnection<()>, String> {
+    let midi = midir::MidiInput::new("Drum City").map_err(|error| error.to_string())?;
+    let port = midi
+        .ports()
+        .into_iter()
+        .find(|port| match (&settings.port, midi.port_name(port)) {
+            (Some(wanted), Ok(name)) => name.contains(wanted.as_str()),
+            (None, _) => true,
+            (Some(_), Err(_)) => false,
+        })
+        .ok_or("no MIDI input found")?;
+    let name = midi.port_name(&port).unwrap_or_default();
+    let connection = midi
+        .connect(
+            &port,
+            "drum-city-input",
+            move |_, message, _| loopback.send(message),
+            (),
+        )
+        .map_err(|error| error.to_string())?;
+    info!("Listening to MIDI input {}", name);
+    Ok(connection)
+}
+
+/// Turns the messages received since the last frame into `MidiNoteOn` events
+fn read_midi(input: Res<MidiInput>, mut notes: EventWriter<MidiNoteOn>) {
+    let messages = input.messages.lock().expect("MIDI input lock was poisoned");
+    notes.send_batch(
+        messages
+            .try_iter()
+            .filter_map(|message| MidiNoteOn::parse(&message)),
+    );
+}
+
+fn midi_lane_presses(
+    time: Res<ControlledTime>,
+    settings: Res<Settings>,
+    song_config: Res<SongConfig>,
+    mut notes: EventReader<MidiNoteOn>,
+    mut presses: EventWriter<LanePressed>,
+) {
+    for note in notes.read() {
+        let Some(direction) = settings.midi.lane(note.note) else {
+            continue;
+        };
+        if song_config.lanes.lanes().contains(&direction) {
+            presses.send(LanePressed {
+                direction,
+                time: time.song_seconds_f64() - settings.input_offset,
+                velocity: Some(note.velocity),
+            });
+        }
+    }
+}
+
+/// Plays lanes with a MIDI instrument, once MIDI input is turned on in the options. Apps that
+/// already have a `MidiInput`, like headless ones with a loopback, keep it instead of opening a
+/// device.
+pub struct MidiPlugin;
+impl Plugin for MidiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MidiNoteOn>()
+            .add_systems(
+                Startup,
+                setup_midi_input.run_if(not(resource_exists::<MidiInput>())),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    connect_midi
+                        .run_if(resource_exists::<MidiDevice>())
+                        .run_if(resource_changed::<Settings>()),
+                    read_midi,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                midi_lane_presses
+                    .in_set(LaneInputSet)
+                    .run_if(in_state(AppState::Game))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .run_if(not(autoplay_enabled)),
+            );
+    }
+}
@@ -0,0 +1,188 @@
+use midly::{Format, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::{midi::GENERAL_MIDI_DRUMS, migrations::FORMAT_VERSION, types::*};
+
+/// Channel that General MIDI keeps for drums, channel 10 counting from 0
+pub const DRUM_CHANNEL: u8 = 9;
+
+/// Tempo of MIDI files until they set one, 120 BPM
+const DEFAULT_TEMPO: f64 = 500_000.;
+
+/// Which notes of a MIDI file become arrows
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// Only reads this track, counting from 0. Every track is read otherwise.
+    pub track: Option<usize>,
+    /// Channel of the drums, counting from 0
+    pub channel: u8,
+    /// Song time in seconds that the MIDI file starts at
+    pub offset: f64,
+    pub difficulty: Option<Difficulty>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            track: None,
+            channel: DRUM_CHANNEL,
+            offset: 0.,
+            difficulty: None,
+        }
+    }
+}
+
+/// Turns MIDI ticks into seconds
+enum TempoMap {
+    /// Ticks are fractions of a beat, so their length follows the tempo changes
+    Metrical {
+        ticks_per_beat: f64,
+        /// Tick, time in seconds and microseconds per beat of each tempo change
+        changes: Vec<(u64, f64, f64)>,
+    },
+    /// Ticks are fractions of a second
+    Timecode { ticks_per_second: f64 },
+}
+
+impl TempoMap {
+    /// `tempos` are the ticks and microseconds per beat of the tempo changes, in order
+    fn new(timing: Timing, tempos: &[(u64, f64)]) -> TempoMap {
+        match timing {
+            Timing::Metrical(ticks_per_beat) => {
+                let ticks_per_beat = ticks_per_beat.as_int() as f64;
+                let mut changes = vec![(0, 0., DEFAULT_TEMPO)];
+                for &(tick, tempo) in tempos {
+                    let (last_tick, last_time, last_tempo) = changes[changes.len() - 1];
+                    let time =
+                        last_time + (tick - last_tick) as f64 / ticks_per_beat * last_tempo / 1e6;
+                    if tick == last_tick {
+                        changes.pop();
+                    }
+                    changes.push((tick, time, tempo));
+                }
+                TempoMap::Metrical {
+                    ticks_per_beat,
+                    changes,
+                }
+            }
+            Timing::Timecode(fps, ticks_per_frame) => TempoMap::Timecode {
+                ticks_per_second: fps.as_f32() as f64 * ticks_per_frame as f64,
+            },
+        }
+    }
+
+    fn seconds(&self, tick: u64) -> f64 {
+        match self {
+            TempoMap::Metrical {
+                ticks_per_beat,
+                changes,
+            } => {
+                let &(change_tick, time, tempo) = changes
+                    .iter()
+                    .rev()
+                    .find(|(change_tick, _, _)| *change_tick <= tick)
+                    .unwrap_or(&changes[0]);
+                time + (tick - change_tick) as f64 / ticks_per_beat * tempo / 1e6
+            }
+            TempoMap::Timecode { ticks_per_second } => tick as f64 / ticks_per_second,
+        }
+    }
+
+    /// Tempo at the start of the file, if it is counted in beats
+    fn bpm(&self) -> Option<f64> {
+        match self {
+            TempoMap::Metrical { changes, .. } => Some(60e6 / changes[0].2),
+            TempoMap::Timecode { .. } => None,
+        }
+    }
+}
+
+fn general_midi_lane(note: u8) -> Option<Directions> {
+    GENERAL_MIDI_DRUMS
+        .iter()
+        .find(|(drum_note, _)| *drum_note == note)
+        .map(|(_, lane)| *lane)
+}
+
+/// Makes a drum chart for the song `filename` from the drum notes of a Standard MIDI File
+pub fn import_chart(
+    midi: &[u8],
+    filename: &str,
+    name: &str,
+    options: &ImportOptions,
+) -> Result<SongConfigToml, String> {
+    let smf = Smf::parse(midi).map_err(|error| format!("Could not read MIDI file: {}", error))?;
+    if smf.header.format == Format::Sequential {
+        return Err("MIDI files with sequential tracks are not supported".to_string());
+    }
+    if let Some(track) = options.track {
+        if track >= smf.tracks.len() {
+            return Err(format!(
+                "The MIDI file only has {} tracks",
+                smf.tracks.len()
+            ));
+        }
+    }
+
+    // tempo changes apply to every track, wherever they are
+    let mut tempos: Vec<(u64, f64)> = Vec::new();
+    let mut notes: Vec<(u64, u8)> = Vec::new();
+    for (index, track) in smf.tracks.iter().enumerate() {
+        let read_notes = options.track.map_or(true, |track| track == index);
+        let mut tick: u64 = 0;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    tempos.push((tick, tempo.as_int() as f64))
+                }
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } if read_notes && channel.as_int() == options.channel && vel.as_int() > 0 => {
+                    notes.push((tick, key.as_int()))
+                }
+                _ => {}
+            }
+        }
+    }
+    tempos.sort_by_key(|(tick, _)| *tick);
+    notes.sort_by_key(|(tick, _)| *tick);
+    let tempo_map = TempoMap::new(smf.header.timing, &tempos);
+
+    let mut arrows: Vec<ArrowTimeToml> = Vec::new();
+    for (tick, note) in notes {
+        let Some(direction) = general_midi_lane(note) else {
+            continue;
+        };
+        // to the millisecond, which keeps the chart readable
+        let click_time = ((tempo_map.seconds(tick) + options.offset) * 1000.).round() / 1000.;
+        // several notes can play the same lane, like the two kick notes
+        let duplicate = arrows
+            .iter()
+            .rev()
+            .take_while(|arrow| arrow.click_time == click_time)
+            .any(|arrow| arrow.direction == direction);
+        if !duplicate {
+            arrows.push(ArrowTimeToml {
+                click_time,
+                speed: Speed::Slow,
+                direction,
+                keysound: None,
+            });
+        }
+    }
+    if arrows.is_empty() {
+        return Err(format!("No drum notes on channel {}", options.channel + 1));
+    }
+
+    Ok(SongConfigToml {
+        format_version: FORMAT_VERSION,
+        name: name.to_string(),
+        filename: filename.to_string(),
+        bpm: tempo_map.bpm(),
+        offset: Some(options.offset),
+        difficulty: options.difficulty,
+        lanes: LaneSet::Drums,
+        arrows,
+    })
+}
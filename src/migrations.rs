@@ -0,0 +1,48 @@
+use toml::{value::Table, Value};
+
+/// Version of the chart format that the game reads and writes. Charts from before versions
+/// existed don't have one, which counts as version 0.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Upgrades a parsed chart file from one version to the next
+pub type Migration = fn(&mut Table);
+
+/// Upgrades a chart file from each version to the next: the first one from version 0 to 1, and
+/// so on. Changing the format means adding a migration here and bumping `FORMAT_VERSION`.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [
+    // version 1 only started numbering the format
+    |_| {},
+];
+
+/// Version of a parsed chart file
+pub fn format_version(chart: &Value) -> Result<u32, String> {
+    match chart.get("format_version") {
+        None => Ok(0),
+        Some(Value::Integer(version)) if *version >= 0 => Ok(*version as u32),
+        Some(_) => Err("format_version must be a whole number".to_string()),
+    }
+}
+
+/// Upgrades a parsed chart file to `FORMAT_VERSION`, and returns the version it had
+pub fn migrate(chart: &mut Value) -> Result<u32, String> {
+    migrate_with(chart, &MIGRATIONS)
+}
+
+/// Upgrades a parsed chart file with a chain of migrations, to the version at the end of the
+/// chain, and returns the version it had
+pub fn migrate_with(chart: &mut Value, migrations: &[Migration]) -> Result<u32, String> {
+    let latest = migrations.len() as u32;
+    let version = format_version(chart)?;
+    if version > latest {
+        return Err(format!(
+            "the chart is in format version {}, but this game only reads up to version {}",
+            version, latest
+        ));
+    }
+    let table = chart.as_table_mut().ok_or("a chart file must be a table")?;
+    for migration in migrations[version as usize..].iter() {
+        migration(table);
+    }
+    table.insert("format_version".to_string(), Value::Integer(latest as i64));
+    Ok(version)
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    arrows::Arrow,
+    chart_gen::Rng,
+    consts::{AppState, SPAWN_POSITION, TARGET_POSITION},
+    types::{ArrowTime, Directions, SongConfig, ARROW_LANES},
+};
+
+/// Playback rates that can be picked, from slowest to fastest
+pub const RATES: [f32; 9] = [0.5, 0.75, 0.9, 1., 1.1, 1.25, 1.5, 1.75, 2.];
+
+/// Share of the arrows' path that hidden and sudden cover
+const LANE_COVER: f32 = 0.4;
+
+/// Changes to how a chart is played, picked on the menu before starting a song
+#[derive(Resource, Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Modifiers {
+    /// Playback speed of the song, which keeps its pitch. Arrows come faster or slower along with
+    /// it.
+    pub rate: f32,
+    /// Flips the lanes upside down
+    pub mirror: bool,
+    /// Swaps the lanes around randomly
+    pub shuffle: bool,
+    /// Seed of the shuffle, kept so that replays get the same lanes
+    pub seed: u64,
+    /// Arrows fade out before they reach the target
+    pub hidden: bool,
+    /// Arrows only appear some way after they spawn
+    pub sudden: bool,
+}
+
+impl Default for Modifiers {
+    fn default() -> Self {
+        Self {
+            rate: 1.,
+            mirror: false,
+            shuffle: false,
+            seed: 1,
+            hidden: false,
+            sudden: false,
+        }
+    }
+}
+
+impl Modifiers {
+    /// Goes to the next playback rate, wrapping around to the slowest
+    pub fn next_rate(&mut self) {
+        self.rate = RATES
+            .iter()
+            .copied()
+            .find(|rate| *rate > self.rate + 0.001)
+            .unwrap_or(RATES[0]);
+    }
+
+    /// Lane each arrow direction of the chart is played on
+    pub fn lanes(&self) -> [Directions; 4] {
+        let mut lanes = ARROW_LANES;
+        if self.mirror {
+            lanes.reverse();
+        }
+        if self.shuffle {
+            let mut rng = Rng::new(self.seed);
+            // Fisher-Yates
+            for i in (1..lanes.len()).rev() {
+                lanes.swap(i, rng.below(i + 1));
+            }
+        }
+        lanes
+    }
+
+    /// Applies the rate and lane changes to a chart's arrows
+    pub fn apply(&self, arrows: &mut [ArrowTime]) {
+        let rate = self.rate.clamp(RATES[0], RATES[RATES.len() - 1]) as f64;
+        let lanes = self.lanes();
+        for arrow in arrows.iter_mut() {
+            let travel_time = arrow.click_time - arrow.spawn_time;
+            arrow.click_time /= rate;
+            arrow.spawn_time = arrow.click_time - travel_time;
+            // drum lanes each have their own sound, so they stay where they are
+            if !arrow.direction.is_drum() {
+                arrow.direction = lanes[arrow.direction.lane()];
+            }
+        }
+    }
+
+    /// Harder modifiers earn more points. Mirror and shuffle only move arrows around, so they
+    /// don't count.
+    pub fn score_multiplier(&self) -> f32 {
+        let mut multiplier = if self.rate < 1. {
+            self.rate
+        } else {
+            1. + (self.rate - 1.) / 2.
+        };
+        if self.hidden {
+            multiplier += 0.1;
+        }
+        if self.sudden {
+            multiplier += 0.1;
+        }
+        multiplier
+    }
+
+    /// Short description for the menu and results, e.g. "1.25x Mirror Hidden"
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.rate != 1. {
+            parts.push(format!("{}x", self.rate));
+        }
+        for (enabled, name) in [
+            (self.mirror, "Mirror"),
+            (self.shuffle, "Shuffle"),
+            (self.hidden, "Hidden"),
+            (self.sudden, "Sudden"),
+        ] {
+            if enabled {
+                parts.push(name.to_string());
+            }
+        }
+        parts.join(" ")
+    }
+}
+
+fn apply_modifiers(modifiers: Res<Modifiers>, mut song_config: ResMut<SongConfig>) {
+    modifiers.apply(&mut song_config.arrows);
+    song_config
+        .arrows
+        .sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
+}
+
+/// Fades arrows in and out for hidden and sudden
+fn cover_lanes(modifiers: Res<Modifiers>, mut query: Query<(&Arrow, &mut Sprite)>) {
+    let path = TARGET_POSITION - SPAWN_POSITION;
+    for (arrow, mut sprite) in query.iter_mut() {
+        let progress = (arrow.position - SPAWN_POSITION) / path;
+
+        let mut alpha: f32 = 1.;
+        if modifiers.sudden {
+            alpha = alpha.min((progress - LANE_COVER) / 0.1);
+        }
+        if modifiers.hidden {
+            alpha = alpha.min((1. - LANE_COVER - progress) / 0.1);
+        }
+        sprite.color.set_a(alpha.clamp(0., 1.));
+    }
+}
+
+pub struct ModifiersPlugin;
+impl Plugin for ModifiersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Modifiers>()
+            .add_systems(OnEnter(AppState::Game), apply_modifiers)
+            .add_systems(
+                Update,
+                cover_lanes
+                    .run_if(in_state(AppState::Game))
+                    .run_if(|modifiers: Res<Modifiers>| modifiers.hidden || modifiers.sudden),
+            );
+    }
+}
@@ -0,0 +1,313 @@
+use bevy::prelude::*;
+
+use crate::{
+    audio::SoundChannel,
+    consts::AppState,
+    menu::{FONT_COLOR, NORMAL_COLOR},
+    settings::{Setting, Settings},
+    types::{Directions, ARROW_LANES, DRUM_LANES},
+};
+
+#[derive(Component)]
+struct OptionsUI;
+
+#[derive(Component, Copy, Clone)]
+enum OptionsButton {
+    /// Moves a setting some steps up or down, see `Settings::change`
+    Change(Setting, i32),
+    /// Waits for the next key press to play a lane with
+    Bind(Directions),
+    Back,
+}
+
+/// Text that shows a setting's value
+#[derive(Component)]
+struct SettingValue(Setting);
+
+/// Lane waiting for a key to be bound to it
+#[derive(Resource, Default)]
+struct Rebinding {
+    lane: Option<Directions>,
+    /// Why the last key pressed for the lane wasn't bound
+    rejected: Option<String>,
+}
+
+/// How a setting is edited on the options screen
+enum Row {
+    /// - and + buttons around the value
+    Stepped(&'static str, Setting),
+    /// A button that shows the value and goes to the next one
+    Cycled(&'static str, Setting),
+    /// A button per lane, of the given width, that waits for a key to bind
+    Keys(&'static str, &'static [Directions], f32),
+}
+
+const ROWS: [Row; 15] = [
+    Row::Stepped("Music", Setting::Volume(SoundChannel::Music)),
+    Row::Stepped("Hit volume", Setting::Volume(SoundChannel::Hits)),
+    Row::Cycled("Hit sounds", Setting::HitSounds),
+    Row::Stepped("Menu sounds", Setting::Volume(SoundChannel::Ui)),
+    Row::Stepped("Audio offset", Setting::AudioOffset),
+    Row::Stepped("Input offset", Setting::InputOffset),
+    Row::Cycled("Scroll speed", Setting::ScrollSpeed),
+    Row::Cycled("Layout", Setting::Layout),
+    Row::Keys("Keys", &ARROW_LANES, 140.),
+    Row::Keys("Drum keys", &DRUM_LANES, 120.),
+    Row::Cycled("MIDI input", Setting::Midi),
+    Row::Cycled("Window", Setting::Resolution),
+    Row::Cycled("Fullscreen", Setting::Fullscreen),
+    Row::Cycled("VSync", Setting::Vsync),
+    Row::Cycled("Antialiasing", Setting::Msaa),
+];
+
+fn value_text(settings: &Settings, rebinding: &Rebinding, setting: Setting) -> String {
+    match setting {
+        Setting::Key(direction) if rebinding.lane == Some(direction) => match &rebinding.rejected {
+            Some(reason) => format!("{}, press another", reason),
+            None => format!("{:?}: press a key", direction),
+        },
+        Setting::Key(direction) => format!("{:?}: {}", direction, settings.describe(setting)),
+        _ => settings.describe(setting),
+    }
+}
+
+fn setup_options(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: FONT_COLOR,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(4.),
+                    ..default()
+                },
+                ..default()
+            },
+            OptionsUI,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Options",
+                TextStyle {
+                    font_size: 32.,
+                    ..style.clone()
+                },
+            ));
+            for row in ROWS.iter() {
+                let name = match row {
+                    Row::Stepped(name, _) | Row::Cycled(name, _) | Row::Keys(name, _, _) => *name,
+                };
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(8.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(name, style.clone()).with_style(
+                            Style {
+                                width: Val::Px(140.),
+                                ..default()
+                            },
+                        ));
+                        match row {
+                            Row::Stepped(_, setting) => {
+                                spawn_button(
+                                    parent,
+                                    &style,
+                                    30.,
+                                    OptionsButton::Change(*setting, -1),
+                                    "-".to_string(),
+                                    None,
+                                );
+                                parent.spawn((
+                                    TextBundle::from_section(
+                                        value_text(&settings, &rebinding, *setting),
+                                        style.clone(),
+                                    )
+                                    .with_style(Style {
+                                        width: Val::Px(100.),
+                                        justify_content: JustifyContent::Center,
+                                        ..default()
+                                    }),
+                                    SettingValue(*setting),
+                                ));
+                                spawn_button(
+                                    parent,
+                                    &style,
+                                    30.,
+                                    OptionsButton::Change(*setting, 1),
+                                    "+".to_string(),
+                                    None,
+                                );
+                            }
+                            Row::Cycled(_, setting) => {
+                                spawn_button(
+                                    parent,
+                                    &style,
+                                    200.,
+                                    OptionsButton::Change(*setting, 1),
+                                    value_text(&settings, &rebinding, *setting),
+                                    Some(*setting),
+                                );
+                            }
+                            Row::Keys(_, lanes, width) => {
+                                for &direction in lanes.iter() {
+                                    let setting = Setting::Key(direction);
+                                    spawn_button(
+                                        parent,
+                                        &style,
+                                        *width,
+                                        OptionsButton::Bind(direction),
+                                        value_text(&settings, &rebinding, setting),
+                                        Some(setting),
+                                    );
+                                }
+                            }
+                        }
+                    });
+            }
+            spawn_button(
+                parent,
+                &style,
+                200.,
+                OptionsButton::Back,
+                "Back".to_string(),
+                None,
+            );
+        });
+}
+
+/// Spawns a button, with its text showing the value of `setting` if there is one
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    style: &TextStyle,
+    width: f32,
+    action: OptionsButton,
+    text: String,
+    setting: Option<Setting>,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(30.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(NORMAL_COLOR),
+                ..default()
+            },
+            action,
+        ))
+        .with_children(|parent| {
+            let mut text = parent.spawn(TextBundle::from_section(text, style.clone()));
+            if let Some(setting) = setting {
+                text.insert(SettingValue(setting));
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn options_buttons(
+    interaction_query: Query<(&Interaction, &OptionsButton), (Changed<Interaction>, With<Button>)>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            match button {
+                OptionsButton::Change(setting, steps) => settings.change(*setting, *steps),
+                OptionsButton::Bind(direction) => {
+                    rebinding.lane = Some(*direction);
+                    rebinding.rejected = None;
+                }
+                OptionsButton::Back => app_state.set(AppState::Menu),
+            }
+        }
+    }
+    if rebinding.lane.is_none() && keyboard_input.just_pressed(KeyCode::Back) {
+        app_state.set(AppState::Menu);
+    }
+}
+
+/// Binds the next key pressed to the lane waiting for one. Keys that can't play it are rejected
+/// and the lane keeps waiting.
+fn rebind_key(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let Some(direction) = rebinding.lane else {
+        return;
+    };
+    if let Some(key) = keyboard_input.get_just_pressed().next() {
+        match settings.key_bindings.bind(direction, *key) {
+            Ok(()) => *rebinding = Rebinding::default(),
+            Err(reason) => rebinding.rejected = Some(reason),
+        }
+    }
+}
+
+fn update_setting_values(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    mut texts: Query<(&mut Text, &SettingValue)>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (mut text, value) in texts.iter_mut() {
+        text.sections[0].value = value_text(&settings, &rebinding, value.0);
+    }
+}
+
+fn exit_options(
+    mut commands: Commands,
+    query: Query<Entity, With<OptionsUI>>,
+    settings: Res<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *rebinding = Rebinding::default();
+    settings.save();
+}
+
+pub struct OptionsPlugin;
+impl Plugin for OptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+            .add_systems(OnEnter(AppState::Options), setup_options)
+            .add_systems(
+                Update,
+                (rebind_key, options_buttons, update_setting_values)
+                    .chain()
+                    .run_if(in_state(AppState::Options)),
+            )
+            .add_systems(OnExit(AppState::Options), exit_options);
+    }
+}
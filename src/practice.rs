@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+
+use crate::{
+    arrows::{Arrow, JudgmentSet, SeekSet, SeekSong},
+    consts::{AppState, BASE_SPEED, DISTANCE, START_TIME_OFFSET},
+    modifiers::{Modifiers, RATES},
+    score::Score,
+    scroll::ScrollSpeed,
+    settings::Settings,
+    time::ControlledTime,
+    types::{ArrowTime, SongConfig},
+};
+
+/// Seconds of song before the section starts, so that even the slowest arrows of the section
+/// spawn after the jump
+const LEAD_IN: f64 = (DISTANCE / BASE_SPEED) as f64;
+
+#[derive(Copy, Clone, Debug)]
+pub struct LoopResult {
+    pub corrects: usize,
+    pub fails: usize,
+    /// Share of the section's points earned, from 0 to 1
+    pub accuracy: f32,
+}
+
+/// Present while practicing a chart: a section of it is played over and over. Nothing played in
+/// practice counts towards high scores.
+#[derive(Resource)]
+pub struct Practice {
+    /// Every arrow of the chart, before modifiers
+    chart: Vec<ArrowTime>,
+    /// Start of the section in chart seconds
+    pub start: f64,
+    /// End of the section in chart seconds
+    pub end: f64,
+    /// Number of the loop being played, 0 before the first one starts
+    pub loop_number: usize,
+    /// Arrows in the loop being played
+    loop_arrows: usize,
+    /// Slowdown picked with the practice keys, in place of the rate modifier. It only applies
+    /// while practicing.
+    pub rate: f32,
+    /// Playback rate of the loop being played. Changes to the rate wait for the next loop.
+    loop_rate: f64,
+    /// Set to start the section over on the next frame
+    restart: bool,
+    pub loops: Vec<LoopResult>,
+}
+
+impl Practice {
+    /// Practices the whole chart until a section is picked
+    pub fn new(song_config: &SongConfig) -> Self {
+        Self {
+            chart: song_config.arrows.clone(),
+            start: 0.,
+            end: last_click_time(&song_config.arrows),
+            loop_number: 0,
+            loop_arrows: 0,
+            rate: 1.,
+            loop_rate: 1.,
+            restart: true,
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn with_section(mut self, start: f64, end: f64) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Takes a reloaded chart, and returns the arrows of the section to play from now on
+    pub fn reload(
+        &mut self,
+        arrows: &[ArrowTime],
+        modifiers: &Modifiers,
+        scroll_speed: &ScrollSpeed,
+    ) -> Vec<ArrowTime> {
+        self.chart = arrows.to_vec();
+        let section = self.section_arrows(&self.loop_modifiers(modifiers), scroll_speed);
+        self.loop_arrows = section.len();
+        section
+    }
+
+    /// The player's modifiers, at the rate of the loop being played
+    pub fn loop_modifiers(&self, modifiers: &Modifiers) -> Modifiers {
+        Modifiers {
+            rate: self.loop_rate as f32,
+            ..*modifiers
+        }
+    }
+
+    fn section_arrows(&self, modifiers: &Modifiers, scroll_speed: &ScrollSpeed) -> Vec<ArrowTime> {
+        let mut arrows: Vec<ArrowTime> = self
+            .chart
+            .iter()
+            .filter(|arrow| arrow.click_time >= self.start && arrow.click_time <= self.end)
+            .copied()
+            .collect();
+        modifiers.apply(&mut arrows);
+        scroll_speed.apply(&mut arrows);
+        arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
+        arrows
+    }
+}
+
+fn last_click_time(arrows: &[ArrowTime]) -> f64 {
+    arrows
+        .iter()
+        .map(|arrow| arrow.click_time)
+        .fold(0., f64::max)
+}
+
+/// Keys to pick the section and the slowdown. Changes apply from the next loop.
+fn practice_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<ControlledTime>,
+    mut practice: ResMut<Practice>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    let chart_time = (time.song_seconds_f64() * practice.loop_rate).max(0.);
+
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        practice.start = chart_time.min(practice.end);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        practice.end = chart_time.max(practice.start);
+    }
+    if keyboard_input.just_pressed(KeyCode::C) {
+        practice.start = 0.;
+        practice.end = last_click_time(&practice.chart);
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        practice.rate = RATES
+            .iter()
+            .rev()
+            .copied()
+            .find(|rate| *rate < practice.rate - 0.001)
+            .unwrap_or(practice.rate);
+    }
+    if keyboard_input.just_pressed(KeyCode::Equals) {
+        practice.rate = RATES
+            .iter()
+            .copied()
+            .find(|rate| *rate > practice.rate + 0.001)
+            .unwrap_or(practice.rate);
+    }
+    if keyboard_input.just_pressed(KeyCode::R) {
+        practice.restart = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        app_state.set(AppState::Menu);
+    }
+}
+
+/// Ends the loop once the section is over and all of its arrows were hit or missed
+fn finish_loop(
+    time: Res<ControlledTime>,
+    song_config: Res<SongConfig>,
+    score: Res<Score>,
+    arrows: Query<&Arrow>,
+    mut practice: ResMut<Practice>,
+) {
+    if practice.restart {
+        return;
+    }
+    let past_end = time.song_seconds_f64() > practice.end / practice.loop_rate;
+    if !(past_end && song_config.arrows.is_empty() && arrows.is_empty()) {
+        return;
+    }
+
+    let accuracy = if practice.loop_arrows == 0 {
+        1.
+    } else {
+        score.get_points() as f32 / (100 * practice.loop_arrows) as f32
+    };
+    practice.loops.push(LoopResult {
+        corrects: score.get_corrects(),
+        fails: score.get_fails(),
+        accuracy,
+    });
+    practice.restart = true;
+}
+
+/// Jumps back to the start of the section
+fn restart_loop(
+    mut practice: ResMut<Practice>,
+    modifiers: Res<Modifiers>,
+    settings: Res<Settings>,
+    mut song_config: ResMut<SongConfig>,
+    mut score: ResMut<Score>,
+    mut seeks: EventWriter<SeekSong>,
+) {
+    if !practice.restart {
+        return;
+    }
+    practice.restart = false;
+    practice.loop_number += 1;
+
+    practice.loop_rate = practice.rate as f64;
+    let modifiers = practice.loop_modifiers(&modifiers);
+    song_config.arrows = practice.section_arrows(&modifiers, &settings.scroll_speed);
+    practice.loop_arrows = song_config.arrows.len();
+
+    let from = practice.start / practice.loop_rate - LEAD_IN;
+    *score = Score::with_multiplier(modifiers.score_multiplier());
+    seeks.send(SeekSong {
+        song_time: from.max(-START_TIME_OFFSET as f64),
+    });
+}
+
+#[derive(Component)]
+struct PracticeText;
+
+fn setup_practice_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            top: Val::Px(60.),
+            ..default()
+        }),
+        PracticeText,
+    ));
+}
+
+fn update_practice_text(practice: Res<Practice>, mut query: Query<&mut Text, With<PracticeText>>) {
+    let mut lines = vec![
+        format!(
+            "Practice {:.1}s - {:.1}s at {}x, loop {}",
+            practice.start, practice.end, practice.rate, practice.loop_number
+        ),
+        "[ ] set section, C clear, -/= speed, R restart, Backspace quit".to_string(),
+    ];
+    if let Some(last) = practice.loops.last() {
+        let best = practice
+            .loops
+            .iter()
+            .map(|result| result.accuracy)
+            .fold(0., f32::max);
+        lines.push(format!(
+            "Last loop: {:.1}% ({} hit, {} missed). Best: {:.1}%",
+            100. * last.accuracy,
+            last.corrects,
+            last.fails,
+            100. * best
+        ));
+    }
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn stop_practice(
+    mut commands: Commands,
+    query: Query<Entity, With<PracticeText>>,
+    practice: Option<Res<Practice>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if practice.is_some() {
+        commands.remove_resource::<Practice>();
+    }
+}
+
+pub struct PracticePlugin;
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::Game),
+            setup_practice_text.run_if(resource_exists::<Practice>()),
+        )
+        .add_systems(
+            Update,
+            (
+                practice_controls,
+                finish_loop,
+                restart_loop,
+                update_practice_text,
+            )
+                .chain()
+                // the last arrows of a loop are judged before it ends
+                .after(JudgmentSet)
+                .before(SeekSet)
+                .run_if(in_state(AppState::Game))
+                .run_if(resource_exists::<Practice>()),
+        )
+        .add_systems(OnExit(AppState::Game), stop_practice);
+    }
+}
@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    arrows::StartPosition,
+    autoplay::{autoplay_enabled, Autoplay},
+    consts::{AppState, REPLAYS_DIR},
+    input::{LaneInputSet, LanePressed},
+    modifiers::Modifiers,
+    practice::Practice,
+    score::Score,
+    storage,
+    time::ControlledTime,
+    types::{Directions, SongConfig},
+};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct ReplayEvent {
+    /// Song time of the press, in seconds
+    pub time: f64,
+    pub direction: Directions,
+    /// See `LanePressed::velocity`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<u8>,
+}
+
+/// Every input of one play of a chart, with the score it earned
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Replay {
+    /// Chart file in `assets/songs`
+    pub chart: String,
+    /// Hash of the chart file the replay was recorded on, see `types::chart_hash`
+    pub chart_hash: String,
+    pub score: usize,
+    pub corrects: usize,
+    pub fails: usize,
+    /// Modifiers the replay was played with, which it has to be watched with too
+    #[serde(default)]
+    pub modifiers: Modifiers,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Replay of the song being played, with the inputs recorded and the score earned so far
+    pub fn recorded(
+        recorder: &ReplayRecorder,
+        song_config: &SongConfig,
+        score: &Score,
+        modifiers: &Modifiers,
+    ) -> Replay {
+        Replay {
+            chart: song_config.path.clone(),
+            chart_hash: song_config.hash.clone(),
+            score: score.get_score(),
+            corrects: score.get_corrects(),
+            fails: score.get_fails(),
+            modifiers: *modifiers,
+            events: recorder.events.clone(),
+        }
+    }
+
+    /// Where the latest replay of a chart is stored
+    pub fn path(chart: &str) -> String {
+        format!("{}/{}", REPLAYS_DIR, chart)
+    }
+
+    pub fn exists(chart: &str) -> bool {
+        storage::exists(&Replay::path(chart))
+    }
+
+    /// Reads the latest replay of a chart. Fails if there is none, or if it can't be parsed, like
+    /// replays saved in an older format.
+    pub fn load(chart: &str) -> Result<Replay, String> {
+        let path = Replay::path(chart);
+        let contents = storage::read(&path).ok_or(format!("{} doesn't exist", path))?;
+        toml::from_str(&contents).map_err(|error| format!("Could not parse {}: {}", path, error))
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string(self).expect("Couldn't convert replay to toml text");
+        storage::write(&Replay::path(&self.chart), &text);
+    }
+}
+
+/// Inputs of the song that is being played
+#[derive(Resource, Default)]
+pub struct ReplayRecorder {
+    events: Vec<ReplayEvent>,
+}
+
+/// Present while a replay is being watched. Its events are fed back as `LanePressed` events
+/// instead of reading the keyboard.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    next_event: usize,
+    /// The player's own modifiers, put back once the replay is over
+    player_modifiers: Modifiers,
+}
+
+impl ReplayPlayback {
+    /// Watches a replay with the modifiers it was recorded with, in place of the player's
+    pub fn new(replay: Replay, modifiers: &mut Modifiers) -> Self {
+        let player_modifiers = std::mem::replace(modifiers, replay.modifiers);
+        Self {
+            replay,
+            next_event: 0,
+            player_modifiers,
+        }
+    }
+}
+
+fn start_recording(mut recorder: ResMut<ReplayRecorder>) {
+    recorder.events.clear();
+}
+
+fn record_presses(mut recorder: ResMut<ReplayRecorder>, mut presses: EventReader<LanePressed>) {
+    for press in presses.read() {
+        recorder.events.push(ReplayEvent {
+            time: press.time,
+            direction: press.direction,
+            velocity: press.velocity,
+        });
+    }
+}
+
+fn save_replay(
+    recorder: Res<ReplayRecorder>,
+    song_config: Res<SongConfig>,
+    score: Res<Score>,
+    modifiers: Res<Modifiers>,
+    autoplay: Res<Autoplay>,
+) {
+    if autoplay.used {
+        return;
+    }
+
+    Replay::recorded(&recorder, &song_config, &score, &modifiers).save();
+}
+
+fn play_back_replay(
+    time: Res<ControlledTime>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut presses: EventWriter<LanePressed>,
+) {
+    let now = time.song_seconds_f64();
+    while let Some(event) = playback.replay.events.get(playback.next_event) {
+        if event.time > now {
+            break;
+        }
+        presses.send(LanePressed {
+            direction: event.direction,
+            time: event.time,
+            velocity: event.velocity,
+        });
+        playback.next_event += 1;
+    }
+}
+
+/// Warns when the chart has changed since the replay was recorded, as it won't play back the same
+fn check_replay_chart(playback: Res<ReplayPlayback>, song_config: Res<SongConfig>) {
+    if playback.replay.chart_hash != song_config.hash {
+        warn!(
+            "Replay of {} was recorded on a different version of the chart",
+            song_config.path
+        );
+    }
+}
+
+/// Checks that watching the replay earned the same score as the run it was recorded from
+fn finish_playback(playback: Res<ReplayPlayback>, score: Res<Score>) {
+    let replay = &playback.replay;
+    let reproduced = (score.get_score(), score.get_corrects(), score.get_fails());
+    let recorded = (replay.score, replay.corrects, replay.fails);
+
+    if reproduced == recorded {
+        info!(
+            "Replay of {} reproduced its score: {:?}",
+            replay.chart, recorded
+        );
+    } else {
+        warn!(
+            "Replay of {} recorded score {:?} but played back as {:?}",
+            replay.chart, recorded, reproduced
+        );
+    }
+}
+
+/// Ends watching a replay, and gives the player back their modifiers
+pub fn stop_playback(
+    mut commands: Commands,
+    playback: Option<Res<ReplayPlayback>>,
+    mut modifiers: ResMut<Modifiers>,
+) {
+    if let Some(playback) = playback {
+        *modifiers = playback.player_modifiers;
+        commands.remove_resource::<ReplayPlayback>();
+    }
+}
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .add_systems(OnEnter(AppState::Game), start_recording)
+            .add_systems(
+                OnEnter(AppState::Game),
+                check_replay_chart.run_if(resource_exists::<ReplayPlayback>()),
+            )
+            .add_systems(
+                Update,
+                record_presses
+                    .after(LaneInputSet)
+                    .run_if(in_state(AppState::Game))
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            )
+            .add_systems(
+                Update,
+                play_back_replay
+                    .in_set(LaneInputSet)
+                    .run_if(in_state(AppState::Game))
+                    .run_if(not(autoplay_enabled))
+                    .run_if(resource_exists::<ReplayPlayback>()),
+            )
+            .add_systems(
+                OnExit(AppState::Game),
+                save_replay
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .run_if(not(resource_exists::<Practice>()))
+                    .run_if(not(resource_exists::<StartPosition>())),
+            )
+            .add_systems(
+                OnEnter(AppState::Results),
+                finish_playback.run_if(resource_exists::<ReplayPlayback>()),
+            )
+            // the results screen still needs to know that this was a replay
+            .add_systems(OnExit(AppState::Results), stop_playback);
+    }
+}
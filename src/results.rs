@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+
+use crate::{
+    consts::AppState,
+    gauge::LifeGauge,
+    high_scores::{record_score, HighScores, LastResult},
+    modifiers::Modifiers,
+    score::Score,
+    types::SongConfig,
+};
+
+#[derive(Component)]
+struct ResultsUI;
+
+#[allow(clippy::too_many_arguments)]
+fn setup_results(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    song_config: Res<SongConfig>,
+    score: Res<Score>,
+    gauge: Res<LifeGauge>,
+    modifiers: Res<Modifiers>,
+    high_scores: Res<HighScores>,
+    last_result: Res<LastResult>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    let title = match song_config.difficulty {
+        Some(difficulty) => format!("{} ({:?})", song_config.name, difficulty),
+        None => song_config.name.clone(),
+    };
+    let mut lines = vec![
+        (title, 40.),
+        (
+            if gauge.failed { "Failed" } else { "Cleared" }.to_string(),
+            30.,
+        ),
+        (format!("{:?}", gauge.grade(&score)), 80.),
+        (format!("Score: {}", score.get_score()), 40.),
+        (
+            format!(
+                "Corrects: {}. Fails: {}",
+                score.get_corrects(),
+                score.get_fails()
+            ),
+            20.,
+        ),
+    ];
+
+    let description = modifiers.describe();
+    if !description.is_empty() {
+        lines.push((
+            format!(
+                "{} (score x{:.2})",
+                description,
+                modifiers.score_multiplier()
+            ),
+            20.,
+        ));
+    }
+
+    let best = high_scores
+        .personal_best(&song_config.hash)
+        .map(|best| best.score);
+    if last_result.new_best {
+        let previous = match last_result.previous_best {
+            Some(previous) => format!(" (was {})", previous),
+            None => String::new(),
+        };
+        lines.push((format!("New personal best!{}", previous), 30.));
+    } else if let Some(best) = best {
+        lines.push((format!("Personal best: {}", best), 30.));
+    }
+    if !last_result.counted {
+        lines.push((
+            "Autoplay, replays and partial songs don't count".to_string(),
+            20.,
+        ));
+    }
+    lines.push(("Press Enter to continue".to_string(), 20.));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            },
+            ResultsUI,
+        ))
+        .with_children(|parent| {
+            for (value, font_size) in lines {
+                parent.spawn(TextBundle::from_section(
+                    value,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ));
+            }
+        });
+}
+
+fn continue_to_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::Space])
+        || mouse_input.just_pressed(MouseButton::Left)
+    {
+        app_state.set(AppState::Menu);
+    }
+}
+
+fn despawn_results(mut commands: Commands, query: Query<Entity, With<ResultsUI>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct ResultsPlugin;
+impl Plugin for ResultsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::Results),
+            // the score has to be recorded before the personal best is looked up
+            setup_results.after(record_score),
+        )
+        .add_systems(Update, continue_to_menu.run_if(in_state(AppState::Results)))
+        .add_systems(OnExit(AppState::Results), despawn_results);
+    }
+}
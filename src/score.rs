@@ -1,19 +1,70 @@
-use bevy::ecs::system::Resource;
+use bevy::ecs::{
+    event::Event,
+    system::{Res, ResMut, Resource},
+};
+use serde_derive::{Deserialize, Serialize};
 
-use crate::consts::THRESHOLD;
+use crate::{consts::THRESHOLD, modifiers::Modifiers, types::Directions};
+
+/// How well an arrow was hit
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Judgment {
+    Perfect,
+    Great,
+    Good,
+    Miss,
+}
+
+impl Judgment {
+    /// Judgment of a press at `distance` from the target, which must be within `THRESHOLD`
+    pub fn from_distance(distance: f32) -> Judgment {
+        let distance = distance.abs();
+        if distance < 0.25 * THRESHOLD {
+            Judgment::Perfect
+        } else if distance < 0.5 * THRESHOLD {
+            Judgment::Great
+        } else {
+            Judgment::Good
+        }
+    }
+}
+
+/// Sent for every arrow that is hit or missed
+#[derive(Event, Copy, Clone, Debug)]
+pub struct ArrowJudged {
+    pub direction: Directions,
+    pub judgment: Judgment,
+    /// See `ArrowTime::keysound`
+    pub keysound: Option<usize>,
+}
 
 #[derive(Resource)]
 pub struct Score {
     score: usize,
+    /// Points before the modifiers' multiplier, which grades are based on
+    points: usize,
+    multiplier: f32,
 
     corrects: usize,
     fails: usize,
 }
 
+impl Default for Score {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Score {
     pub fn new() -> Self {
+        Score::with_multiplier(1.)
+    }
+
+    pub fn with_multiplier(multiplier: f32) -> Self {
         Score {
             score: 0,
+            points: 0,
+            multiplier,
             corrects: 0,
             fails: 0,
         }
@@ -25,9 +76,11 @@ impl Score {
 
         let score_multiplier = (THRESHOLD - distance.abs()) / THRESHOLD;
         let points = (100.0 * score_multiplier).min(100.).max(10.) as usize;
-        self.score += points;
+        self.points += points;
 
-        points
+        let earned = (points as f32 * self.multiplier).round() as usize;
+        self.score += earned;
+        earned
     }
 
     pub fn incr_failed(&mut self) {
@@ -39,6 +92,10 @@ impl Score {
         self.score
     }
 
+    pub fn get_points(&self) -> usize {
+        self.points
+    }
+
     pub fn get_corrects(&self) -> usize {
         self.corrects
     }
@@ -47,3 +104,8 @@ impl Score {
         self.fails
     }
 }
+
+/// Starts every song from a clean score
+pub fn reset_score(mut score: ResMut<Score>, modifiers: Res<Modifiers>) {
+    *score = Score::with_multiplier(modifiers.score_multiplier());
+}
@@ -0,0 +1,69 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{consts::DISTANCE, types::ArrowTime, types::Speed};
+
+/// How fast arrows move across the screen, picked by the player. Only the look changes: presses
+/// are judged in chart time, so scores are the same at any scroll speed.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+// toml can't hold variants with values, so they're saved as e.g. `{ kind = "XMod", value = 1.5 }`
+#[serde(tag = "kind", content = "value")]
+pub enum ScrollSpeed {
+    /// Multiplies the speed of every arrow
+    XMod(f32),
+    /// Every arrow is on screen for this many seconds, whatever its speed in the chart
+    CMod(f32),
+}
+
+impl Default for ScrollSpeed {
+    fn default() -> Self {
+        ScrollSpeed::XMod(1.)
+    }
+}
+
+/// Scroll speeds that can be picked, in order
+pub const SCROLL_SPEEDS: [ScrollSpeed; 10] = [
+    ScrollSpeed::XMod(0.5),
+    ScrollSpeed::XMod(0.75),
+    ScrollSpeed::XMod(1.),
+    ScrollSpeed::XMod(1.5),
+    ScrollSpeed::XMod(2.),
+    ScrollSpeed::XMod(3.),
+    ScrollSpeed::CMod(3.),
+    ScrollSpeed::CMod(2.),
+    ScrollSpeed::CMod(1.5),
+    ScrollSpeed::CMod(1.),
+];
+
+impl ScrollSpeed {
+    /// Speed in x coordinate values per second of an arrow with this chart speed
+    pub fn velocity(&self, speed: Speed) -> f32 {
+        match self {
+            ScrollSpeed::XMod(multiplier) => speed.value() * multiplier,
+            ScrollSpeed::CMod(seconds) => DISTANCE / seconds,
+        }
+    }
+
+    /// Sets how fast arrows move and, so that they still reach the target on time, when they spawn
+    pub fn apply(&self, arrows: &mut [ArrowTime]) {
+        for arrow in arrows.iter_mut() {
+            arrow.velocity = self.velocity(arrow.speed);
+            arrow.spawn_time = arrow.click_time - (DISTANCE / arrow.velocity) as f64;
+        }
+    }
+
+    /// Goes to the next scroll speed, wrapping around to the first
+    pub fn next(&self) -> ScrollSpeed {
+        let index = SCROLL_SPEEDS
+            .iter()
+            .position(|speed| speed == self)
+            .map_or(0, |index| (index + 1) % SCROLL_SPEEDS.len());
+        SCROLL_SPEEDS[index]
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ScrollSpeed::XMod(multiplier) => format!("{}x", multiplier),
+            ScrollSpeed::CMod(seconds) => format!("C{}s", seconds),
+        }
+    }
+}
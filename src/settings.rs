@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    audio::SoundChannel,
+    consts::SETTINGS_FILE,
+    display::{DisplaySettings, MSAA_SAMPLES},
+    hit_sounds::HitSoundSettings,
+    input::KeyBindings,
+    layout::Layout,
+    midi::MidiSettings,
+    scroll::ScrollSpeed,
+    storage,
+    types::Directions,
+};
+
+/// Largest audio or input offset, in seconds either way
+const MAX_OFFSET: f64 = 0.5;
+
+/// Step of the offsets on the options screen, in seconds
+const OFFSET_STEP: f64 = 0.005;
+
+/// Step of the volumes on the options screen and of the volume keys
+const VOLUME_STEP: f32 = 0.1;
+
+/// The player's settings, saved in `SETTINGS_FILE` and edited on the options screen
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// From 0 to 1. Settings files from before there were several volumes call it `volume`.
+    #[serde(alias = "volume")]
+    pub music_volume: f32,
+    /// Volume of hit sounds and keysounds, from 0 to 1
+    pub hit_volume: f32,
+    /// Volume of menu sounds, from 0 to 1
+    pub ui_volume: f32,
+    /// Silences every channel, without losing their volumes
+    pub muted: bool,
+    /// Seconds the music is started early by, to make up for audio latency. Raise it if arrows
+    /// reach their targets after the beat.
+    pub audio_offset: f64,
+    /// Seconds taken off every key press, to make up for input latency. Raise it if presses
+    /// are judged late.
+    pub input_offset: f64,
+    pub scroll_speed: ScrollSpeed,
+    pub layout: Layout,
+    pub key_bindings: KeyBindings,
+    pub display: DisplaySettings,
+    pub hit_sounds: HitSoundSettings,
+    pub midi: MidiSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music_volume: 1.,
+            hit_volume: 0.8,
+            ui_volume: 0.6,
+            muted: false,
+            audio_offset: 0.,
+            input_offset: 0.,
+            scroll_speed: ScrollSpeed::default(),
+            layout: Layout::default(),
+            key_bindings: KeyBindings::default(),
+            display: DisplaySettings::default(),
+            hit_sounds: HitSoundSettings::default(),
+            midi: MidiSettings::default(),
+        }
+    }
+}
+
+/// One entry of the options screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Setting {
+    Volume(SoundChannel),
+    HitSounds,
+    AudioOffset,
+    InputOffset,
+    ScrollSpeed,
+    Layout,
+    Key(Directions),
+    Resolution,
+    Fullscreen,
+    Vsync,
+    Msaa,
+    Midi,
+}
+
+impl Settings {
+    /// Reads the settings file. A file that can't be parsed is set aside and the defaults are used.
+    pub fn load() -> Settings {
+        match storage::read(SETTINGS_FILE) {
+            Some(contents) => toml::from_str(&contents).unwrap_or_else(|error| {
+                let broken = storage::set_aside(SETTINGS_FILE);
+                warn!(
+                    "Could not parse {}, so the settings start over. It was kept as {}: {}",
+                    SETTINGS_FILE, broken, error
+                );
+                Settings::default()
+            }),
+            None => Settings::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string(self).expect("Couldn't convert settings to toml text");
+        storage::write(SETTINGS_FILE, &text);
+    }
+
+    /// Volume that sounds of a channel play at
+    pub fn volume(&self, channel: SoundChannel) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.channel_volume(channel)
+        }
+    }
+
+    /// Volume of a channel, whether muted or not
+    pub fn channel_volume(&self, channel: SoundChannel) -> f32 {
+        match channel {
+            SoundChannel::Music => self.music_volume,
+            SoundChannel::Hits => self.hit_volume,
+            SoundChannel::Ui => self.ui_volume,
+        }
+    }
+
+    /// Moves a setting `steps` steps up or down. Settings that aren't numbers go to their next
+    /// value whichever way they're moved. Key bindings are set with `KeyBindings::set` instead.
+    pub fn change(&mut self, setting: Setting, steps: i32) {
+        let offset = |offset: f64| {
+            let offset = offset + steps as f64 * OFFSET_STEP;
+            // in whole steps, so that repeated changes don't drift
+            ((offset / OFFSET_STEP).round() * OFFSET_STEP).clamp(-MAX_OFFSET, MAX_OFFSET)
+        };
+        match setting {
+            Setting::Volume(channel) => {
+                let volume = match channel {
+                    SoundChannel::Music => &mut self.music_volume,
+                    SoundChannel::Hits => &mut self.hit_volume,
+                    SoundChannel::Ui => &mut self.ui_volume,
+                };
+                let changed = *volume + steps as f32 * VOLUME_STEP;
+                *volume = ((changed / VOLUME_STEP).round() * VOLUME_STEP).clamp(0., 1.);
+            }
+            Setting::HitSounds => self.hit_sounds.next_mode(),
+            Setting::AudioOffset => self.audio_offset = offset(self.audio_offset),
+            Setting::InputOffset => self.input_offset = offset(self.input_offset),
+            Setting::ScrollSpeed => self.scroll_speed = self.scroll_speed.next(),
+            Setting::Layout => self.layout = self.layout.next(),
+            Setting::Key(_) => {}
+            Setting::Resolution => self.display.next_resolution(),
+            Setting::Fullscreen => self.display.fullscreen = !self.display.fullscreen,
+            Setting::Vsync => self.display.vsync = !self.display.vsync,
+            Setting::Msaa => {
+                let index = MSAA_SAMPLES
+                    .iter()
+                    .position(|samples| *samples == self.display.msaa)
+                    .map_or(0, |index| (index + 1) % MSAA_SAMPLES.len());
+                self.display.msaa = MSAA_SAMPLES[index];
+            }
+            Setting::Midi => self.midi.enabled = !self.midi.enabled,
+        }
+    }
+
+    /// Value of a setting as shown on the options screen
+    pub fn describe(&self, setting: Setting) -> String {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+        match setting {
+            Setting::Volume(channel) => format!("{:.0}%", 100. * self.channel_volume(channel)),
+            Setting::HitSounds => self.hit_sounds.describe_mode().to_string(),
+            Setting::AudioOffset => format!("{:+.0} ms", 1000. * self.audio_offset),
+            Setting::InputOffset => format!("{:+.0} ms", 1000. * self.input_offset),
+            Setting::ScrollSpeed => self.scroll_speed.describe(),
+            Setting::Layout => self.layout.describe().to_string(),
+            Setting::Key(direction) => format!("{:?}", self.key_bindings.key(direction)),
+            Setting::Resolution => self.display.describe_resolution(),
+            Setting::Fullscreen => on_off(self.display.fullscreen),
+            Setting::Vsync => on_off(self.display.vsync),
+            Setting::Msaa => match self.display.msaa {
+                1 => "off".to_string(),
+                samples => format!("{}x", samples),
+            },
+            Setting::Midi => on_off(self.midi.enabled),
+        }
+    }
+}
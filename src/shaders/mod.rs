@@ -7,8 +7,6 @@ use bevy::{
     sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle},
 };
 
-use crate::consts::{WINDOW_HEIGHT, WINDOW_WIDTH};
-
 // #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 // pub struct CustomMaterial {}
 
@@ -49,7 +47,8 @@ pub struct ShadersPlugin;
 impl Plugin for ShadersPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(Material2dPlugin::<CustomMaterial>::default())
-            .add_systems(Startup, setup);
+            .add_systems(Startup, setup)
+            .add_systems(Update, fit_background);
     }
 }
 
@@ -66,17 +65,37 @@ fn setup(
     //     material: materials.add(CustomMaterial {}),
     //     ..default()
     // });
-    commands.spawn(MaterialMesh2dBundle {
-        // mesh: meshes.add(shape::Plane { size: 3.0 }.into()).into(),
-        mesh: meshes.add(Mesh::from(shape::Quad::default())).into(),
-        transform: Transform::default().with_scale(Vec3::new(WINDOW_WIDTH, WINDOW_HEIGHT, 0.)),
-        // material: materials.add(ColorMaterial::from(Color::TURQUOISE)),
-        material: materials.add(CustomMaterial {
-            color: Color::BLUE,
-            color_texture: Some(asset_server.load("images/space.png")),
-        }),
-        ..default()
-    });
+    commands.spawn((
+        MaterialMesh2dBundle {
+            // mesh: meshes.add(shape::Plane { size: 3.0 }.into()).into(),
+            mesh: meshes.add(Mesh::from(shape::Quad::default())).into(),
+            // sized to the window by fit_background
+            transform: Transform::default(),
+            // material: materials.add(ColorMaterial::from(Color::TURQUOISE)),
+            material: materials.add(CustomMaterial {
+                color: Color::BLUE,
+                color_texture: Some(asset_server.load("images/space.png")),
+            }),
+            ..default()
+        },
+        Background,
+    ));
+}
+
+#[derive(Component)]
+struct Background;
+
+/// Stretches the background over everything the camera shows
+fn fit_background(
+    cameras: Query<&OrthographicProjection, Changed<OrthographicProjection>>,
+    mut backgrounds: Query<&mut Transform, With<Background>>,
+) {
+    for projection in cameras.iter() {
+        let size = projection.area.size();
+        for mut transform in backgrounds.iter_mut() {
+            transform.scale = Vec3::new(size.x, size.y, 0.);
+        }
+    }
 }
 
 // Background
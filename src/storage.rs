@@ -0,0 +1,104 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{create_dir_all, read_to_string, rename, File},
+    io::Write,
+    path::Path,
+};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::{JsCast, JsValue};
+
+// Settings, scores, replays and charts are saved under file names relative to the working
+// directory. Browsers have no files to write, so there the same names are keys of the page's
+// local storage instead.
+
+/// Reads a save, or `None` if nothing was saved under that name yet
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(name: &str) -> Option<String> {
+    if !Path::new(name).exists() {
+        return None;
+    }
+    Some(read_to_string(name).unwrap_or_else(|error| panic!("Could not read {}: {}", name, error)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(name: &str, text: &str) {
+    if let Some(folder) = Path::new(name).parent() {
+        create_dir_all(folder)
+            .unwrap_or_else(|error| panic!("Couldn't create {:?}: {}", folder, error));
+    }
+    let mut file =
+        File::create(name).unwrap_or_else(|error| panic!("Couldn't open {}: {}", name, error));
+    file.write_all(text.as_bytes())
+        .unwrap_or_else(|error| panic!("Couldn't write to {}: {}", name, error));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn exists(name: &str) -> bool {
+    Path::new(name).exists()
+}
+
+/// Moves a save that couldn't be read out of the way, so that saving again doesn't lose it.
+/// Returns where it was moved to.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_aside(name: &str) -> String {
+    let broken = format!("{}.broken", name);
+    rename(name, &broken)
+        .unwrap_or_else(|error| panic!("Couldn't move {} to {}: {}", name, broken, error));
+    broken
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> web_sys::Storage {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .expect("The browser has no local storage")
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(name: &str) -> Option<String> {
+    local_storage()
+        .get_item(name)
+        .expect("Could not read from local storage")
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(name: &str, text: &str) {
+    local_storage()
+        .set_item(name, text)
+        .expect("Couldn't write to local storage");
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn exists(name: &str) -> bool {
+    read(name).is_some()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn set_aside(name: &str) -> String {
+    let broken = format!("{}.broken", name);
+    if let Some(text) = read(name) {
+        write(&broken, &text);
+    }
+    local_storage()
+        .remove_item(name)
+        .expect("Couldn't write to local storage");
+    broken
+}
+
+/// Offers a file to the player as a download, for saves they'd want out of the browser
+#[cfg(target_arch = "wasm32")]
+pub fn download(file_name: &str, text: &str) {
+    let parts = js_sys::Array::of1(&JsValue::from_str(text));
+    let blob = web_sys::Blob::new_with_str_sequence(&parts).expect("Couldn't make the download");
+    let url = web_sys::Url::create_object_url_with_blob(&blob).expect("Couldn't make the download");
+    let link: web_sys::HtmlAnchorElement = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|element| element.dyn_into().ok())
+        .expect("Couldn't make the download link");
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+    let _ = web_sys::Url::revoke_object_url(&url);
+}
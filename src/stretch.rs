@@ -0,0 +1,215 @@
+use std::{collections::VecDeque, time::Duration};
+
+use rodio::Source;
+
+/// Length of the pieces the audio is cut into, in seconds
+const SEGMENT: f32 = 0.04;
+/// How far a piece can move from where it would ideally be taken, in seconds
+const TOLERANCE: f32 = 0.01;
+/// Only every few samples are compared when lining pieces up, to keep it cheap
+const CORRELATION_STRIDE: usize = 4;
+
+/// Plays a source faster or slower without changing its pitch. This is WSOLA (waveform
+/// similarity overlap-add): overlapping pieces of the source are taken further apart, or closer
+/// together, than they're played. Each piece is shifted a little so that its waveform lines up
+/// with the one before it.
+pub struct TimeStretch<S: Source<Item = i16>> {
+    source: S,
+    rate: f32,
+    channels: usize,
+    sample_rate: u32,
+    /// Samples read from the source and still needed, interleaved
+    input: VecDeque<f32>,
+    /// Frame number of the first frame in `input`
+    start: usize,
+    finished: bool,
+    /// Hann window over a piece. Windows half a piece apart add up to one.
+    window: Vec<f32>,
+    /// Frames between pieces in the output, half a piece
+    hop: usize,
+    tolerance: usize,
+    /// Number of the next piece
+    piece: usize,
+    /// Frame the last piece was taken from
+    previous: Option<usize>,
+    /// Second half of the last piece, which the next one is added to
+    tail: Vec<f32>,
+    output: VecDeque<i16>,
+}
+
+impl<S: Source<Item = i16>> TimeStretch<S> {
+    /// Plays `source` at `rate` times its speed
+    pub fn new(source: S, rate: f32) -> Self {
+        let channels = source.channels().max(1) as usize;
+        let sample_rate = source.sample_rate();
+        let hop = ((sample_rate as f32 * SEGMENT) as usize / 2).max(1);
+        let length = hop * 2;
+        let window = (0..length)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / length as f32).cos())
+            .collect();
+        Self {
+            source,
+            rate,
+            channels,
+            sample_rate,
+            input: VecDeque::new(),
+            start: 0,
+            finished: false,
+            window,
+            hop,
+            tolerance: (sample_rate as f32 * TOLERANCE) as usize,
+            piece: 0,
+            previous: None,
+            tail: vec![0.; hop * channels],
+            output: VecDeque::new(),
+        }
+    }
+
+    fn passthrough(&self) -> bool {
+        (self.rate - 1.).abs() < 0.001
+    }
+
+    /// Reads the source until `frame` is in `input`, or the source ends
+    fn fill(&mut self, frame: usize) {
+        while !self.finished && self.start + self.input.len() / self.channels <= frame {
+            for _ in 0..self.channels {
+                match self.source.next() {
+                    Some(sample) => self.input.push_back(sample as f32),
+                    None => {
+                        self.finished = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// A sample of the source, silence past its end
+    fn sample(&self, frame: usize, channel: usize) -> f32 {
+        frame
+            .checked_sub(self.start)
+            .and_then(|frame| self.input.get(frame * self.channels + channel))
+            .copied()
+            .unwrap_or(0.)
+    }
+
+    /// All channels of a frame together, for comparing waveforms
+    fn mono(&self, frame: usize) -> f32 {
+        (0..self.channels)
+            .map(|channel| self.sample(frame, channel))
+            .sum()
+    }
+
+    /// Frame near `ideal` where the source looks most like it does at `natural`, which is where
+    /// the last piece would have carried on
+    fn best_match(&self, ideal: usize, natural: usize) -> usize {
+        let first = ideal.saturating_sub(self.tolerance).max(self.start);
+        let mut best = (f32::MIN, ideal);
+        for candidate in first..=ideal + self.tolerance {
+            let mut correlation = 0.;
+            let mut energy = 0.;
+            for i in (0..self.hop).step_by(CORRELATION_STRIDE) {
+                let sample = self.mono(candidate + i);
+                correlation += sample * self.mono(natural + i);
+                energy += sample * sample;
+            }
+            let similarity = correlation / energy.sqrt().max(1.);
+            if similarity > best.0 {
+                best = (similarity, candidate);
+            }
+        }
+        best.1
+    }
+
+    /// Adds the next piece to the output. False once the source has run out.
+    fn next_piece(&mut self) -> bool {
+        let length = self.window.len();
+        let ideal = (self.piece as f64 * self.hop as f64 * self.rate as f64).round() as usize;
+        self.fill(ideal + self.tolerance + length);
+        if self.finished && ideal >= self.start + self.input.len() / self.channels {
+            if self.tail.is_empty() {
+                return false;
+            }
+            let tail = std::mem::take(&mut self.tail);
+            self.output.extend(tail.into_iter().map(to_sample));
+            return true;
+        }
+
+        let position = match self.previous {
+            Some(previous) => self.best_match(ideal, previous + self.hop),
+            None => ideal,
+        };
+        let mut tail = Vec::with_capacity(self.hop * self.channels);
+        for i in 0..length {
+            for channel in 0..self.channels {
+                let sample = self.sample(position + i, channel) * self.window[i];
+                if i < self.hop {
+                    let overlap = self.tail[i * self.channels + channel];
+                    self.output.push_back(to_sample(overlap + sample));
+                } else {
+                    tail.push(sample);
+                }
+            }
+        }
+        self.tail = tail;
+        self.previous = Some(position);
+        self.piece += 1;
+
+        // the next piece is taken near its ideal frame, and compared with what follows this one
+        let next_ideal = (self.piece as f64 * self.hop as f64 * self.rate as f64).round() as usize;
+        let needed = next_ideal
+            .saturating_sub(self.tolerance)
+            .min(position + self.hop);
+        while self.start < needed && !self.input.is_empty() {
+            self.input.drain(..self.channels.min(self.input.len()));
+            self.start += 1;
+        }
+        true
+    }
+}
+
+fn to_sample(sample: f32) -> i16 {
+    sample.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+impl<S: Source<Item = i16>> Iterator for TimeStretch<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.passthrough() {
+            return self.source.next();
+        }
+        loop {
+            if let Some(sample) = self.output.pop_front() {
+                return Some(sample);
+            }
+            if !self.next_piece() {
+                return None;
+            }
+        }
+    }
+}
+
+impl<S: Source<Item = i16>> Source for TimeStretch<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.passthrough() {
+            self.source.current_frame_len()
+        } else {
+            None
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source
+            .total_duration()
+            .map(|duration| duration.div_f32(self.rate))
+    }
+}
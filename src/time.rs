@@ -4,6 +4,21 @@ use bevy::{
     utils::{Duration, Instant},
 };
 
+/// Time since the Unix epoch, from the system clock. `SystemTime` panics in the browser, which
+/// has its own clock.
+pub fn unix_time() -> Duration {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        Duration::from_secs_f64(js_sys::Date::now() / 1000.)
+    }
+}
+
 #[derive(Resource)]
 pub struct ControlledTime {
     delta: Duration,
@@ -12,6 +27,10 @@ pub struct ControlledTime {
     delta_seconds: f32,
     seconds_since_startup: f64,
     startup: Instant,
+    /// When set, time only moves forward through `advance` instead of following the real clock
+    manual: bool,
+    /// Time added by `advance` that the next `update` applies
+    pending: Duration,
 }
 impl Default for ControlledTime {
     fn default() -> Self {
@@ -22,6 +41,8 @@ impl Default for ControlledTime {
             delta_seconds_f64: 0.0,
             seconds_since_startup: 0.0,
             delta_seconds: 0.0,
+            manual: false,
+            pending: Duration::from_secs(0),
         }
     }
 }
@@ -29,15 +50,54 @@ impl Default for ControlledTime {
 // TODO: port to how 0.12 Bevy thinks of Time (Time::new_with might also help)
 impl ControlledTime {
     pub fn reset_time(&mut self) {
-        self.startup = Instant::now();
+        self.startup = self.now();
         self.seconds_since_startup = 0.0;
     }
 
+    /// Jumps to a position in the song, see `song_seconds_f64`. Positions before the
+    /// countdown start it from its beginning.
+    pub fn reset_time_at(&mut self, song_seconds: f64) {
+        let elapsed = Duration::from_secs_f64((song_seconds + START_TIME_OFFSET as f64).max(0.));
+        let now = self.now();
+        self.startup = now - elapsed;
+        // a manual clock that was never updated would otherwise measure from the new startup
+        self.last_update.get_or_insert(now);
+        self.seconds_since_startup = elapsed.as_secs_f64();
+    }
+
     pub fn update(&mut self) {
-        let now = Instant::now();
+        let now = if self.manual {
+            self.now() + std::mem::take(&mut self.pending)
+        } else {
+            Instant::now()
+        };
         self.update_with_instant(now);
     }
 
+    /// Detaches this clock from real time, for deterministic simulations.
+    /// Afterwards only `advance` moves it forward.
+    pub fn use_manual_clock(&mut self) {
+        self.manual = true;
+    }
+
+    /// Moves a manual clock forward on its next update
+    pub fn advance(&mut self, delta: Duration) {
+        self.pending += delta;
+    }
+
+    /// Time since the last reset, with the full precision of `Duration`
+    pub fn elapsed(&self) -> Duration {
+        self.now() - self.startup
+    }
+
+    fn now(&self) -> Instant {
+        if self.manual {
+            self.last_update.unwrap_or(self.startup)
+        } else {
+            Instant::now()
+        }
+    }
+
     pub fn update_with_instant(&mut self, instant: Instant) {
         if let Some(last_update) = self.last_update {
             self.delta = instant - last_update;
@@ -73,6 +133,12 @@ impl ControlledTime {
     pub fn elapsed_seconds_f64(&self) -> f64 {
         self.seconds_since_startup
     }
+
+    /// Position in the song in seconds. Negative during the countdown before the audio plays.
+    #[inline]
+    pub fn song_seconds_f64(&self) -> f64 {
+        self.seconds_since_startup - START_TIME_OFFSET as f64
+    }
 }
 
 // ----- //
@@ -1,48 +1,45 @@
-use crate::consts::*;
+use crate::{chart::Chart, consts::*, migrations};
 use bevy::{
     asset::{AssetServer, Handle},
     audio::AudioSource,
-    ecs::{
-        system::Resource,
-        world::{FromWorld, World},
-    },
-    input::{keyboard::KeyCode, Input},
+    ecs::system::Resource,
 };
 use core::f32::consts::PI;
 use serde_derive::{Deserialize, Serialize};
 use std::{fs::File, io::Read};
 
+/// A lane that arrows come down: one of the four arrows, or a piece of the drum kit
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Directions {
     Up,
     Down,
     Left,
     Right,
+    Kick,
+    Snare,
+    HiHat,
+    Tom,
+    Crash,
 }
 
-impl Directions {
-    /// Checks if the key corresponding to this direction was just pressed
-    pub fn key_just_pressed(&self, input: &Input<KeyCode>) -> bool {
-        match self {
-            Directions::Up => input.just_pressed(KeyCode::Up),
-            Directions::Down => input.just_pressed(KeyCode::Down),
-            Directions::Left => input.just_pressed(KeyCode::Left),
-            Directions::Right => input.just_pressed(KeyCode::Right),
-        }
-    }
-
-    /// Checks if a key that corresponds to this direction is currently being pressed
-    pub fn key_pressed(&self, input: &Input<KeyCode>) -> bool {
-        let keys = match self {
-            Directions::Up => [KeyCode::Up],
-            Directions::Down => [KeyCode::Down],
-            Directions::Left => [KeyCode::Left],
-            Directions::Right => [KeyCode::Right],
-        };
+/// Lanes of arrow charts, from the top
+pub const ARROW_LANES: [Directions; 4] = [
+    Directions::Up,
+    Directions::Down,
+    Directions::Left,
+    Directions::Right,
+];
 
-        keys.iter().any(|code| input.pressed(*code))
-    }
+/// Lanes of drum charts, in order across the kit
+pub const DRUM_LANES: [Directions; 5] = [
+    Directions::HiHat,
+    Directions::Snare,
+    Directions::Kick,
+    Directions::Tom,
+    Directions::Crash,
+];
 
+impl Directions {
     /// Returns the correct rotation for an arrow with this direction
     pub fn rotation(&self) -> f32 {
         match self {
@@ -50,18 +47,70 @@ impl Directions {
             Directions::Down => -PI * 0.5,
             Directions::Left => PI,
             Directions::Right => 0.,
+            // drum notes are round
+            _ => 0.,
         }
     }
 
-    /// Returns the correct y-coordinate for an arrow with this direction
-    pub fn y(&self) -> f32 {
+    /// Position of this direction's lane in `ARROW_LANES` or `DRUM_LANES`
+    pub fn lane(&self) -> usize {
         match self {
-            Directions::Up => 150.,
-            Directions::Down => 50.,
-            Directions::Left => -50.,
-            Directions::Right => -150.,
+            Directions::Up => 0,
+            Directions::Down => 1,
+            Directions::Left => 2,
+            Directions::Right => 3,
+            Directions::HiHat => 0,
+            Directions::Snare => 1,
+            Directions::Kick => 2,
+            Directions::Tom => 3,
+            Directions::Crash => 4,
         }
     }
+
+    pub fn is_drum(&self) -> bool {
+        !ARROW_LANES.contains(self)
+    }
+
+    /// Sample in `assets/sounds` that a drum lane plays when pressed
+    pub fn drum_sample(&self) -> Option<&'static str> {
+        match self {
+            Directions::Kick => Some("kick.wav"),
+            Directions::Snare => Some("snare.wav"),
+            Directions::HiHat => Some("hihat.wav"),
+            Directions::Tom => Some("tom.wav"),
+            Directions::Crash => Some("crash.wav"),
+            _ => None,
+        }
+    }
+}
+
+/// Which lanes a chart is played on
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaneSet {
+    #[default]
+    Arrows,
+    /// The drum kit, see `DRUM_LANES`
+    Drums,
+}
+
+impl LaneSet {
+    pub fn lanes(&self) -> &'static [Directions] {
+        match self {
+            LaneSet::Arrows => &ARROW_LANES,
+            LaneSet::Drums => &DRUM_LANES,
+        }
+    }
+
+    pub fn is_arrows(&self) -> bool {
+        *self == LaneSet::Arrows
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -90,16 +139,25 @@ impl Speed {
 #[derive(Copy, Clone, Debug)]
 pub struct ArrowTime {
     pub spawn_time: f64,
+    pub click_time: f64,
     pub speed: Speed,
+    /// Speed the arrow moves across the screen at, which the player's scroll speed changes.
+    /// Presses are judged with `speed`.
+    pub velocity: f32,
     pub direction: Directions,
+    /// Index in `SongConfig::keysounds` of the sample played when this arrow is hit
+    pub keysound: Option<usize>,
 }
 
 impl ArrowTime {
-    fn new_from_toml(a: &ArrowTimeToml) -> Self {
+    fn new_from_toml(a: &ArrowTimeToml, keysound: Option<usize>) -> Self {
         Self {
             spawn_time: a.click_time - (DISTANCE / a.speed.value()) as f64,
+            click_time: a.click_time,
             speed: a.speed,
+            velocity: a.speed.value(),
             direction: a.direction,
+            keysound,
         }
     }
 }
@@ -107,59 +165,163 @@ impl ArrowTime {
 #[derive(Debug, Resource)]
 pub struct SongConfig {
     pub name: String,
+    /// Chart file in `assets/songs` that this was loaded from
+    pub path: String,
+    /// Content hash of the chart file, see `chart_hash`
+    pub hash: String,
+    /// The chart asset, kept so that edits to the file reload the song
+    pub chart: Handle<Chart>,
+    pub difficulty: Option<Difficulty>,
+    pub lanes: LaneSet,
     pub song_audio: Handle<AudioSource>,
+    /// Samples that the chart's arrows play when hit, see `ArrowTimeToml::keysound`
+    pub keysounds: Vec<Handle<AudioSource>>,
     pub arrows: Vec<ArrowTime>,
 }
 
-pub fn load_config(path: &str, asset_server: &AssetServer) -> SongConfig {
-    // For WASM, fetch a remote file
-    // https://rustwasm.github.io/wasm-bindgen/examples/fetch.html
+/// Reads a chart file from `assets/songs`. The game loads charts through the `AssetServer`
+/// instead, see `chart::Chart`, and this is for the command line tools, which don't run in the
+/// browser.
+pub fn read_chart_file(path: &str) -> String {
     let mut file = File::open(format!("assets/songs/{}", path)).expect("Could not open file");
     let mut contents = String::new();
     file.read_to_string(&mut contents)
         .expect("Could not read file into string");
+    contents
+}
 
-    let parsed: SongConfigToml =
-        toml::from_str(&contents).expect("Could not parse into SongConfigToml");
+/// Reads and parses a chart file from `assets/songs`
+pub fn read_config(path: &str) -> SongConfigToml {
+    parse_config(&read_chart_file(path)).expect("Could not parse into SongConfigToml")
+}
 
-    let mut arrows = parsed
+/// Parses the text of a chart file, upgrading it first if it's in an older format version
+pub fn parse_config(contents: &str) -> Result<SongConfigToml, String> {
+    let mut chart: toml::Value = toml::from_str(contents).map_err(|error| error.to_string())?;
+    migrations::migrate(&mut chart)?;
+    let config: SongConfigToml = chart.try_into().map_err(|error| error.to_string())?;
+    // e.g. drum notes in a chart of arrows, which would have no lane to be played on
+    let lanes = config.lanes.lanes();
+    if let Some(arrow) = config
         .arrows
         .iter()
-        .map(|a| ArrowTime::new_from_toml(a))
-        .collect::<Vec<ArrowTime>>();
-
-    // Sort by spawn_time
-    arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
-
-    // TODO: what is &* about
-    let song_audio = asset_server.load(format!("songs/{}", parsed.filename));
+        .find(|arrow| !lanes.contains(&arrow.direction))
+    {
+        return Err(format!(
+            "the {:?} arrow at {}s is not on one of the chart's {:?} lanes",
+            arrow.direction, arrow.click_time, config.lanes
+        ));
+    }
+    Ok(config)
+}
 
-    SongConfig {
-        name: parsed.name,
-        arrows,
-        song_audio,
+/// Identifies the exact contents of a chart file, so that replays and scores made on one version
+/// of a chart are not mixed up with another. This is a 64 bit FNV-1a hash, which is stable across
+/// platforms and Rust versions (unlike `DefaultHasher`).
+pub fn chart_hash(contents: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in contents.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
     }
+    format!("{:016x}", hash)
 }
 
-// The approach here it to create a handle to the material, so that arrows share a reference vs each having their own copy.
-impl FromWorld for SongConfig {
-    fn from_world(world: &mut World) -> Self {
-        let asset_server = world.resource::<AssetServer>();
+impl SongConfig {
+    /// Sets up the song of a loaded chart, which came from `path` in `assets/songs`
+    pub fn from_chart(
+        path: &str,
+        handle: Handle<Chart>,
+        chart: &Chart,
+        asset_server: &AssetServer,
+    ) -> SongConfig {
+        let parsed = &chart.config;
+
+        // each sample is loaded once, however many arrows play it
+        let mut keysound_files: Vec<&str> = Vec::new();
+        let mut arrows = parsed
+            .arrows
+            .iter()
+            .map(|arrow| {
+                let keysound = arrow.keysound.as_deref().map(|file| {
+                    keysound_files
+                        .iter()
+                        .position(|loaded| *loaded == file)
+                        .unwrap_or_else(|| {
+                            keysound_files.push(file);
+                            keysound_files.len() - 1
+                        })
+                });
+                ArrowTime::new_from_toml(arrow, keysound)
+            })
+            .collect::<Vec<ArrowTime>>();
+        let keysounds = keysound_files
+            .iter()
+            .map(|file| asset_server.load(format!("sounds/{}", file)))
+            .collect();
+
+        // Sort by spawn_time
+        arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
 
-        load_config("akisey-dance.toml", asset_server)
+        let song_audio = asset_server.load(format!("songs/{}", parsed.filename));
+
+        SongConfig {
+            name: parsed.name.clone(),
+            path: path.to_string(),
+            hash: chart.hash.clone(),
+            chart: handle,
+            difficulty: parsed.difficulty,
+            lanes: parsed.lanes,
+            arrows,
+            song_audio,
+            keysounds,
+        }
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SongConfigToml {
+    /// Version of the chart format, see `migrations::FORMAT_VERSION`. Charts without one are from
+    /// before versions existed.
+    #[serde(default)]
+    pub format_version: u32,
     pub name: String,
     pub filename: String,
+    /// Tempo of the song, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    /// Seconds from the start of the song to the first beat, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Difficulty>,
+    /// Drum charts set this to `"Drums"`, and their arrows use the drum lanes
+    #[serde(default, skip_serializing_if = "LaneSet::is_arrows")]
+    pub lanes: LaneSet,
     pub arrows: Vec<ArrowTimeToml>,
 }
 
+impl Default for SongConfigToml {
+    fn default() -> Self {
+        Self {
+            format_version: migrations::FORMAT_VERSION,
+            name: String::new(),
+            filename: String::new(),
+            bpm: None,
+            offset: None,
+            difficulty: None,
+            lanes: LaneSet::Arrows,
+            arrows: Vec::new(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ArrowTimeToml {
     pub click_time: f64,
     pub speed: Speed,
     pub direction: Directions,
+    /// Sample in `assets/sounds` played when the arrow is hit, instead of the hit sound
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keysound: Option<String>,
 }
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::{consts::*, score::Score, time::ControlledTime};
+use crate::{consts::*, gauge::LifeGauge, score::Score, time::ControlledTime};
 
 #[derive(Component)]
 struct UI;
@@ -43,16 +43,19 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 .insert(TimeText {});
         });
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                position_type: PositionType::Absolute,
-                left: Val::Px(10.),
-                bottom: Val::Px(10.),
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::NONE),
                 ..Default::default()
             },
-            background_color: BackgroundColor(Color::NONE),
-            ..Default::default()
-        })
+            UI,
+        ))
         .with_children(|parent| {
             parent
                 .spawn(TextBundle {
@@ -71,6 +74,66 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 })
                 .insert(ScoreText {});
         });
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexEnd,
+                    row_gap: Val::Px(5.),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::NONE),
+                ..Default::default()
+            },
+            UI,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(200.),
+                        height: Val::Px(20.),
+                        border: UiRect::all(Val::Px(2.)),
+                        ..Default::default()
+                    },
+                    border_color: BorderColor(Color::rgb(0.9, 0.9, 0.9)),
+                    background_color: BackgroundColor(Color::rgb(0.15, 0.15, 0.15)),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(50.),
+                                height: Val::Percent(100.),
+                                ..Default::default()
+                            },
+                            background_color: BackgroundColor(Color::rgb(0.35, 0.75, 0.35)),
+                            ..Default::default()
+                        })
+                        .insert(LifeBar);
+                });
+            parent
+                .spawn(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: "Grade: S".to_string(),
+                            style: TextStyle {
+                                font: font.clone(),
+                                font_size: 40.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(GradeText);
+        });
 }
 
 fn update_time_text(time: Res<ControlledTime>, mut query: Query<(&mut Text, &TimeText)>) {
@@ -101,6 +164,32 @@ fn update_score_text(score: Res<Score>, mut query: Query<(&mut Text, &ScoreText)
     }
 }
 
+#[derive(Component)]
+struct LifeBar;
+
+#[derive(Component)]
+struct GradeText;
+
+fn update_life_gauge(
+    gauge: Res<LifeGauge>,
+    score: Res<Score>,
+    mut bars: Query<(&mut Style, &mut BackgroundColor), With<LifeBar>>,
+    mut texts: Query<&mut Text, With<GradeText>>,
+) {
+    for (mut style, mut color) in bars.iter_mut() {
+        style.width = Val::Percent(100. * gauge.life);
+        // turns red when the song is about to be failed
+        *color = if gauge.life < 0.25 {
+            BackgroundColor(Color::rgb(0.8, 0.2, 0.2))
+        } else {
+            BackgroundColor(Color::rgb(0.35, 0.75, 0.35))
+        };
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("Grade: {:?}", gauge.running_grade(&score));
+    }
+}
+
 fn despawn_ui(mut commands: Commands, query: Query<(Entity, &UI)>) {
     for (entity, _) in query.iter() {
         commands.entity(entity).despawn_recursive();
@@ -113,6 +202,7 @@ impl Plugin for UIPlugin {
         app.add_systems(OnEnter(AppState::Game), setup_ui)
             .add_systems(Update, update_time_text.run_if(in_state(AppState::Game)))
             .add_systems(Update, update_score_text.run_if(in_state(AppState::Game)))
+            .add_systems(Update, update_life_gauge.run_if(in_state(AppState::Game)))
             .add_systems(OnExit(AppState::Game), despawn_ui);
     }
 }
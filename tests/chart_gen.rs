@@ -0,0 +1,126 @@
+use drum_city::{
+    analysis::{analyse, AudioData},
+    chart_gen::{generate_arrows, GeneratorOptions},
+    types::{parse_config, Difficulty, LaneSet, SongConfigToml},
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const BPM: f64 = 120.;
+/// Song time of the first beat
+const FIRST_BEAT: f64 = 0.25;
+
+/// Twenty seconds of clicks: loud ones on the beat and softer ones on the eighth notes between
+fn click_track() -> AudioData {
+    let beat = 60. / BPM;
+    let mut samples = vec![0.; SAMPLE_RATE as usize * 20];
+    let mut time = FIRST_BEAT;
+    let mut on_beat = true;
+    while time < 19.5 {
+        let start = (time * SAMPLE_RATE as f64) as usize;
+        let loudness = if on_beat { 0.9 } else { 0.4 };
+        // a short burst of a high tone that dies away
+        for i in 0..SAMPLE_RATE as usize / 50 {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            samples[start + i] =
+                loudness * (t * 2000. * std::f32::consts::TAU).sin() * (-t * 200.).exp();
+        }
+        time += beat / 2.;
+        on_beat = !on_beat;
+    }
+    AudioData {
+        samples,
+        sample_rate: SAMPLE_RATE,
+    }
+}
+
+fn options(difficulty: Difficulty) -> GeneratorOptions {
+    GeneratorOptions {
+        difficulty,
+        density: None,
+        seed: 7,
+    }
+}
+
+#[test]
+fn the_beat_of_a_click_track_is_found() {
+    let analysis = analyse(&click_track());
+
+    assert!(
+        (analysis.bpm - BPM).abs() < 2.,
+        "found {} BPM",
+        analysis.bpm
+    );
+    // the first beat, give or take a frame, or a whole beat later
+    let phase = (analysis.offset - FIRST_BEAT).rem_euclid(analysis.beat_period());
+    let off_by = phase.min(analysis.beat_period() - phase);
+    assert!(off_by < 0.03, "first beat at {}s", analysis.offset);
+    // every click is an onset
+    assert!(
+        (analysis.onsets.len() as i64 - 77).abs() <= 3,
+        "{} onsets",
+        analysis.onsets.len()
+    );
+}
+
+#[test]
+fn harder_difficulties_have_more_arrows() {
+    let analysis = analyse(&click_track());
+    let counts: Vec<usize> = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
+        .into_iter()
+        .map(|difficulty| generate_arrows(&analysis, &options(difficulty)).len())
+        .collect();
+    assert!(counts[0] > 0);
+    assert!(
+        counts[0] < counts[1] && counts[1] < counts[2],
+        "{:?} arrows",
+        counts
+    );
+}
+
+#[test]
+fn generated_patterns_avoid_awkward_repeats() {
+    let analysis = analyse(&click_track());
+    for seed in 0..20 {
+        let arrows = generate_arrows(
+            &analysis,
+            &GeneratorOptions {
+                seed,
+                ..options(Difficulty::Hard)
+            },
+        );
+        for pair in arrows.windows(2) {
+            let quick = pair[1].click_time - pair[0].click_time < analysis.beat_period();
+            assert!(!(quick && pair[0].direction == pair[1].direction));
+        }
+        for run in arrows.windows(3) {
+            assert!(
+                !(run[0].direction == run[1].direction && run[1].direction == run[2].direction)
+            );
+        }
+        // ping-ponging between two directions
+        for run in arrows.windows(4) {
+            assert!(
+                !(run[0].direction == run[2].direction && run[1].direction == run[3].direction)
+            );
+        }
+    }
+}
+
+#[test]
+fn generated_charts_load_like_any_other() {
+    let analysis = analyse(&click_track());
+    let chart = SongConfigToml {
+        name: "Clicks".to_string(),
+        filename: "clicks.ogg".to_string(),
+        bpm: Some(analysis.bpm),
+        offset: Some(analysis.offset),
+        difficulty: Some(Difficulty::Medium),
+        lanes: LaneSet::Arrows,
+        arrows: generate_arrows(&analysis, &options(Difficulty::Medium)),
+        ..Default::default()
+    };
+
+    let loaded = parse_config(&toml::to_string(&chart).unwrap()).unwrap();
+    assert_eq!(loaded.arrows.len(), chart.arrows.len());
+    assert!(loaded.arrows.iter().all(|arrow| arrow.click_time >= 1.));
+}
@@ -0,0 +1,91 @@
+use drum_city::{
+    export::ExportFormat,
+    types::{parse_config, ArrowTimeToml, Directions, LaneSet, SongConfigToml, Speed},
+};
+
+fn chart(arrows: &[(f64, Directions)]) -> SongConfigToml {
+    SongConfigToml {
+        name: "Song".to_string(),
+        filename: "song.ogg".to_string(),
+        bpm: Some(120.),
+        offset: Some(0.5),
+        arrows: arrows
+            .iter()
+            .map(|&(click_time, direction)| ArrowTimeToml {
+                click_time,
+                speed: Speed::Slow,
+                direction,
+                keysound: None,
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn charts_are_quantized_to_their_bpm() {
+    // at 120 BPM a measure is 2 seconds and an eighth note 0.25
+    let config = chart(&[
+        (0.5, Directions::Left),
+        // a little off the beat, as tapped in the Map Maker
+        (0.76, Directions::Down),
+        (1.49, Directions::Up),
+        (1.5, Directions::Right),
+        (2.5, Directions::Right),
+    ]);
+
+    let sm = ExportFormat::Sm.export(&config).unwrap();
+    assert!(sm.contains("#MUSIC:song.ogg;"));
+    assert!(sm.contains("#OFFSET:-0.500;"));
+    assert!(sm.contains("#BPMS:0.000=120.000;"));
+    let measures = sm.split("0.000,0.000,0.000,0.000,0.000:\n").nth(1).unwrap();
+    assert_eq!(
+        measures,
+        "1000\n0100\n0000\n0000\n0011\n0000\n0000\n0000\n,\n0001\n0000\n0000\n0000\n;\n"
+    );
+
+    let osu = ExportFormat::Osu.export(&config).unwrap();
+    assert!(osu.contains("AudioFilename: song.ogg"));
+    assert!(osu.contains("500,500,4,1,0,100,1,0"));
+    let hit_objects = osu.split("[HitObjects]\n").nth(1).unwrap();
+    assert_eq!(
+        hit_objects,
+        "64,192,500,1,0,0:0:0:0:\n\
+         192,192,750,1,0,0:0:0:0:\n\
+         320,192,1500,1,0,0:0:0:0:\n\
+         448,192,1500,1,0,0:0:0:0:\n\
+         448,192,2500,1,0,0:0:0:0:\n"
+    );
+}
+
+#[test]
+fn only_arrow_charts_with_a_bpm_are_exported() {
+    let mut config = chart(&[(0.5, Directions::Left)]);
+    config.bpm = None;
+    assert!(ExportFormat::Sm.export(&config).is_err());
+
+    let mut config = chart(&[(0.5, Directions::Kick)]);
+    config.lanes = LaneSet::Drums;
+    assert!(ExportFormat::Osu.export(&config).is_err());
+}
+
+#[test]
+fn drum_notes_in_an_arrow_chart_are_an_error_not_a_crash() {
+    // lanes default to arrows
+    let text = r#"
+name = "Song"
+filename = "song.ogg"
+bpm = 120.0
+
+[[arrows]]
+click_time = 1.0
+speed = "Slow"
+direction = "Kick"
+"#;
+    let error = parse_config(text).unwrap_err();
+    assert!(error.contains("Kick"), "{}", error);
+
+    let config = chart(&[(0.5, Directions::Left), (1., Directions::Kick)]);
+    let error = ExportFormat::Sm.export(&config).unwrap_err();
+    assert!(error.contains("Kick"), "{}", error);
+}
@@ -0,0 +1,357 @@
+use bevy::{asset::Assets, input::keyboard::KeyCode};
+use drum_city::{
+    arrows::StartPosition,
+    chart::Chart,
+    consts::{AppState, START_TIME_OFFSET},
+    gauge::Grade,
+    headless::Simulation,
+    modifiers::Modifiers,
+    scroll::ScrollSpeed,
+    settings::Settings,
+    types::{read_config, Directions, SongConfig},
+};
+
+const CHART: &str = "akisey-dance.toml";
+const DRUM_CHART: &str = "akisey-dance-drums.toml";
+
+/// Click times of the chart, with the directions that have to be pressed together at each one
+fn chords(chart: &str) -> Vec<(f64, Vec<Directions>)> {
+    let mut chords: Vec<(f64, Vec<Directions>)> = Vec::new();
+    for arrow in read_config(chart).arrows {
+        match chords.last_mut() {
+            Some((time, directions)) if *time == arrow.click_time => {
+                directions.push(arrow.direction)
+            }
+            _ => chords.push((arrow.click_time, vec![arrow.direction])),
+        }
+    }
+    chords
+}
+
+fn arrow_count(chart: &str) -> usize {
+    read_config(chart).arrows.len()
+}
+
+#[test]
+fn pressing_every_arrow_on_time_scores_a_perfect_run() {
+    let mut simulation = Simulation::new(CHART);
+    for (time, directions) in chords(CHART) {
+        simulation.run_until(time);
+        simulation.press(&directions);
+    }
+    simulation.run_for(5.);
+
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), arrow_count(CHART));
+    assert_eq!(score.get_fails(), 0);
+    assert_eq!(score.get_score(), 100 * arrow_count(CHART));
+
+    let gauge = simulation.life_gauge();
+    assert!(!gauge.failed);
+    assert!(gauge.life > gauge.settings.start);
+    assert_eq!(gauge.grade(simulation.score()), Grade::S);
+}
+
+#[test]
+fn missing_arrows_drains_the_life_gauge_until_the_song_is_failed() {
+    let mut simulation = Simulation::new(CHART);
+    simulation.run_for(20.);
+
+    // medium starts at half life and loses a tenth per miss
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), 0);
+    assert_eq!(score.get_fails(), 5);
+    assert_eq!(score.get_score(), 0);
+    assert_eq!(simulation.state(), AppState::Results);
+
+    let gauge = simulation.life_gauge();
+    assert!(gauge.failed);
+    assert_eq!(gauge.grade(simulation.score()), Grade::D);
+}
+
+#[test]
+fn slightly_late_press_earns_fewer_points() {
+    let (time, directions) = chords(CHART)[0].clone();
+
+    let mut simulation = Simulation::new(CHART);
+    // a Slow arrow moves 200 units per second, so this is 10 of the 20 units of margin
+    simulation.run_until(time + 0.05);
+    simulation.press(&directions);
+
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), 1);
+    assert_eq!(score.get_score(), 50);
+}
+
+#[test]
+fn early_or_wrong_presses_do_not_hit() {
+    let (time, directions) = chords(CHART)[0].clone();
+    assert_eq!(directions, vec![Directions::Up]);
+
+    let mut simulation = Simulation::new(CHART);
+    simulation.run_until(time - 0.5);
+    simulation.press(&directions);
+    simulation.run_until(time);
+    simulation.press(&[Directions::Down]);
+    simulation.run_until(time + 3.);
+
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), 0);
+    assert_eq!(score.get_fails(), 1);
+}
+
+#[test]
+fn autoplay_clears_every_chart() {
+    for chart in [CHART, "akisey-dance-medium.toml", DRUM_CHART] {
+        let mut simulation = Simulation::new(chart);
+        simulation.enable_autoplay();
+        simulation.run_for(1000.);
+
+        assert_eq!(simulation.state(), AppState::Results);
+        let score = simulation.score();
+        assert_eq!(score.get_corrects(), arrow_count(chart), "{}", chart);
+        assert_eq!(score.get_fails(), 0, "{}", chart);
+        assert_eq!(score.get_score(), 100 * arrow_count(chart), "{}", chart);
+    }
+}
+
+#[test]
+fn drum_lanes_are_played_and_judged_like_arrows() {
+    let mut simulation = Simulation::new(DRUM_CHART);
+    let chords = chords(DRUM_CHART);
+    // arrow keys don't play drum charts
+    simulation.run_until(chords[0].0);
+    simulation.press(&[Directions::Up, Directions::Left]);
+    assert_eq!(simulation.score().get_corrects(), 0);
+
+    for (time, directions) in chords {
+        simulation.run_until(time);
+        simulation.press(&directions);
+    }
+    simulation.run_for(5.);
+
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), arrow_count(DRUM_CHART));
+    assert_eq!(score.get_fails(), 0);
+    assert_eq!(score.get_score(), 100 * arrow_count(DRUM_CHART));
+}
+
+#[test]
+fn scroll_speed_only_changes_how_arrows_look() {
+    let (time, directions) = chords(CHART)[0].clone();
+
+    for scroll_speed in [ScrollSpeed::XMod(2.), ScrollSpeed::CMod(1.)] {
+        let settings = Settings {
+            scroll_speed,
+            ..Default::default()
+        };
+        let mut simulation = Simulation::with_resource(CHART, settings);
+        // the same press as above is judged the same, however fast the arrow moved
+        simulation.run_until(time + 0.05);
+        simulation.press(&directions);
+        assert_eq!(simulation.score().get_score(), 50);
+
+        simulation.run_for(20.);
+        assert_eq!(simulation.score().get_corrects(), 1);
+    }
+}
+
+#[test]
+fn modifiers_change_timing_and_lanes_but_autoplay_still_clears() {
+    let modifiers = Modifiers {
+        rate: 1.5,
+        mirror: true,
+        shuffle: true,
+        seed: 7,
+        hidden: true,
+        ..Default::default()
+    };
+    let mut simulation = Simulation::with_resource(CHART, modifiers);
+    simulation.enable_autoplay();
+    simulation.run_for(1000.);
+
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), arrow_count(CHART));
+    assert_eq!(score.get_points(), 100 * arrow_count(CHART));
+    // 1.5x rate is worth 1.25, hidden another 0.1
+    assert_eq!(score.get_score(), 135 * arrow_count(CHART));
+}
+
+#[test]
+fn faster_rate_moves_click_times_earlier() {
+    let (time, directions) = chords(CHART)[0].clone();
+    let modifiers = Modifiers {
+        rate: 2.,
+        ..Default::default()
+    };
+
+    let mut simulation = Simulation::with_resource(CHART, modifiers);
+    simulation.run_until(time / 2.);
+    simulation.press(&directions);
+
+    assert_eq!(simulation.score().get_corrects(), directions.len());
+}
+
+#[test]
+fn practice_loops_a_section_and_keeps_each_loops_accuracy() {
+    let (start, end) = (5., 11.);
+    let section = read_config(CHART)
+        .arrows
+        .iter()
+        .filter(|arrow| arrow.click_time >= start && arrow.click_time <= end)
+        .count();
+
+    let mut simulation = Simulation::practice(CHART, start, end);
+    simulation.enable_autoplay();
+    // a minute, longer than the whole song, which would have ended by now
+    simulation.run_frames(60 * 60);
+
+    assert_eq!(simulation.state(), AppState::Game);
+    let practice = simulation.practice_state();
+    assert!(practice.loops.len() >= 3);
+    for result in &practice.loops {
+        assert_eq!(result.corrects, section);
+        assert_eq!(result.fails, 0);
+        assert_eq!(result.accuracy, 1.);
+    }
+}
+
+#[test]
+fn practice_slowdown_is_kept_out_of_the_modifiers() {
+    let mut simulation = Simulation::practice(CHART, 5., 11.);
+    simulation.tap_key(KeyCode::Minus);
+    simulation.tap_key(KeyCode::Minus);
+    assert_eq!(simulation.practice_state().rate, 0.75);
+    // normal play after practice still has the rate picked on the menu
+    assert_eq!(simulation.app.world.resource::<Modifiers>().rate, 1.);
+
+    // the slowdown applies from the next loop, which spreads the section's arrows out
+    simulation.tap_key(KeyCode::R);
+    let arrows = &simulation.app.world.resource::<SongConfig>().arrows;
+    assert!(arrows
+        .iter()
+        .all(|arrow| arrow.click_time >= 5. / 0.75 && arrow.click_time <= 11. / 0.75));
+}
+
+#[test]
+fn starting_part_way_skips_earlier_arrows_and_spawns_those_in_flight() {
+    let start = 6.;
+    let chart = read_config(CHART);
+    let remaining = chart
+        .arrows
+        .iter()
+        .filter(|arrow| arrow.click_time >= start)
+        .count();
+    // arrows take 2 to 3 seconds from spawning to the target, so some are already on screen
+    let in_flight = chart
+        .arrows
+        .iter()
+        .filter(|arrow| arrow.click_time >= start && arrow.click_time < start + 2.)
+        .count();
+    assert!(in_flight > 0);
+
+    let mut simulation = Simulation::with_resource(CHART, StartPosition(start));
+    assert!((simulation.song_time() - start).abs() < 0.1);
+    assert!(simulation.arrows_on_screen() >= in_flight);
+
+    simulation.enable_autoplay();
+    simulation.run_for(1000.);
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), remaining);
+    assert_eq!(score.get_fails(), 0);
+}
+
+#[test]
+fn starting_before_the_countdown_starts_at_the_countdown() {
+    let mut simulation = Simulation::with_resource(CHART, StartPosition(-10.));
+    assert_eq!(simulation.state(), AppState::Game);
+    assert!(simulation.song_time() >= -(START_TIME_OFFSET as f64));
+    simulation.enable_autoplay();
+    simulation.run_for(1000.);
+    assert_eq!(simulation.score().get_corrects(), arrow_count(CHART));
+}
+
+#[test]
+fn editing_the_chart_mid_song_reloads_the_arrows_still_to_come() {
+    let cut = 10.;
+    let kept = read_config(CHART)
+        .arrows
+        .iter()
+        .filter(|arrow| arrow.click_time < cut)
+        .count();
+    assert!(kept < arrow_count(CHART));
+
+    let mut simulation = Simulation::new(CHART);
+    simulation.enable_autoplay();
+    simulation.run_until(6.);
+
+    // the same change as saving the file, which the file watcher picks up
+    let handle = simulation.app.world.resource::<SongConfig>().chart.clone();
+    let mut charts = simulation.app.world.resource_mut::<Assets<Chart>>();
+    let chart = charts.get_mut(&handle).unwrap();
+    chart.config.arrows.retain(|arrow| arrow.click_time < cut);
+
+    simulation.run_for(1000.);
+    assert_eq!(simulation.state(), AppState::Results);
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), kept);
+    assert_eq!(score.get_fails(), 0);
+}
+
+/// Makes the chart being played count as changed, like saving its file unchanged
+fn touch_chart(simulation: &mut Simulation) {
+    let handle = simulation.app.world.resource::<SongConfig>().chart.clone();
+    let mut charts = simulation.app.world.resource_mut::<Assets<Chart>>();
+    charts.get_mut(&handle).unwrap();
+}
+
+#[test]
+fn reloading_the_chart_judges_every_arrow_once() {
+    let (time, directions) = chords(CHART)[0].clone();
+
+    // hit early, then reloaded while the arrow would still be on its way
+    let mut simulation = Simulation::new(CHART);
+    simulation.run_until(time - 0.05);
+    simulation.press(&directions);
+    touch_chart(&mut simulation);
+    simulation.enable_autoplay();
+    simulation.run_for(1000.);
+    assert_eq!(simulation.score().get_corrects(), arrow_count(CHART));
+    assert_eq!(simulation.score().get_fails(), 0);
+
+    // reloaded while the arrow can still be hit late, then missed like without the reload
+    let mut missed = Simulation::new(CHART);
+    missed.run_until(time + 3.);
+    assert!(missed.score().get_fails() > 0);
+    let mut simulation = Simulation::new(CHART);
+    simulation.run_until(time + 0.05);
+    touch_chart(&mut simulation);
+    simulation.run_until(time + 3.);
+    assert_eq!(simulation.score().get_fails(), missed.score().get_fails());
+}
+
+#[test]
+fn watching_a_replay_reproduces_its_score_exactly() {
+    // early, late and missed presses, to earn a score that's anything but perfect
+    let mut recording = Simulation::recording(CHART);
+    for (i, (time, directions)) in chords(CHART).into_iter().enumerate() {
+        if i % 4 == 3 {
+            continue;
+        }
+        let offset = [-0.06, 0.03, 0.08][i % 3];
+        recording.run_until(time + offset);
+        recording.press(&directions);
+    }
+    recording.run_for(1000.);
+    assert_eq!(recording.state(), AppState::Results);
+    let replay = recording.recorded_replay();
+    assert!(replay.corrects > 0 && replay.fails > 0);
+
+    let (score, corrects, fails) = (replay.score, replay.corrects, replay.fails);
+    let mut watching = Simulation::watch(CHART, replay);
+    watching.run_for(1000.);
+    assert_eq!(watching.state(), AppState::Results);
+    assert_eq!(watching.score().get_score(), score);
+    assert_eq!(watching.score().get_corrects(), corrects);
+    assert_eq!(watching.score().get_fails(), fails);
+}
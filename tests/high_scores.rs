@@ -0,0 +1,102 @@
+use drum_city::{
+    high_scores::{HighScore, HighScores},
+    types::{Difficulty, SongConfig},
+};
+
+fn song_config(hash: &str) -> SongConfig {
+    SongConfig {
+        name: "Song".to_string(),
+        path: "song.toml".to_string(),
+        hash: hash.to_string(),
+        chart: Default::default(),
+        difficulty: Some(Difficulty::Hard),
+        lanes: Default::default(),
+        song_audio: Default::default(),
+        keysounds: Vec::new(),
+        arrows: Vec::new(),
+    }
+}
+
+fn high_score(score: usize) -> HighScore {
+    HighScore {
+        score,
+        corrects: 0,
+        fails: 0,
+        grade: None,
+        failed: false,
+        modifiers: Default::default(),
+        played_at: 0,
+    }
+}
+
+#[test]
+fn personal_best_is_kept_per_chart_version() {
+    let mut high_scores = HighScores::default();
+
+    assert!(high_scores.add(&song_config("a"), high_score(500)));
+    assert!(!high_scores.add(&song_config("a"), high_score(300)));
+    assert!(!high_scores.add(&song_config("a"), high_score(500)));
+    assert!(high_scores.add(&song_config("a"), high_score(800)));
+    // an edited chart has a different hash and starts over
+    assert!(high_scores.add(&song_config("b"), high_score(100)));
+
+    assert_eq!(
+        high_scores.personal_best("a").map(|best| best.score),
+        Some(800)
+    );
+    assert_eq!(
+        high_scores.personal_best("b").map(|best| best.score),
+        Some(100)
+    );
+
+    let scores: Vec<usize> = high_scores.charts["a"]
+        .scores
+        .iter()
+        .map(|score| score.score)
+        .collect();
+    assert_eq!(scores, vec![800, 500, 500, 300]);
+}
+
+#[test]
+fn failed_runs_are_not_personal_bests() {
+    let mut high_scores = HighScores::default();
+    let failed = |score| HighScore {
+        failed: true,
+        ..high_score(score)
+    };
+
+    assert!(!high_scores.add(&song_config("a"), failed(900)));
+    assert_eq!(high_scores.personal_best("a"), None);
+    assert!(high_scores.add(&song_config("a"), high_score(300)));
+    assert!(!high_scores.add(&song_config("a"), failed(1000)));
+    assert!(high_scores.add(&song_config("a"), high_score(400)));
+
+    assert_eq!(
+        high_scores.personal_best("a").map(|best| best.score),
+        Some(400)
+    );
+    // failed runs are kept below every cleared one
+    let scores: Vec<(usize, bool)> = high_scores.charts["a"]
+        .scores
+        .iter()
+        .map(|score| (score.score, score.failed))
+        .collect();
+    assert_eq!(
+        scores,
+        vec![(400, false), (300, false), (1000, true), (900, true)]
+    );
+}
+
+#[test]
+fn rewritten_charts_keep_their_scores() {
+    let mut high_scores = HighScores::default();
+    high_scores.add(&song_config("old"), high_score(500));
+
+    assert!(high_scores.rehash("old", "new"));
+    assert_eq!(high_scores.personal_best("old"), None);
+    assert_eq!(
+        high_scores.personal_best("new").map(|best| best.score),
+        Some(500)
+    );
+    assert!(!high_scores.rehash("missing", "new"));
+}
@@ -0,0 +1,41 @@
+use drum_city::{
+    consts::{SPAWN_POSITION, TARGET_POSITION},
+    layout::Layout,
+    types::Directions,
+};
+
+#[test]
+fn layouts_place_lanes_and_targets() {
+    let horizontal = Layout::horizontal();
+    let lanes: Vec<f32> = [
+        Directions::Up,
+        Directions::Down,
+        Directions::Left,
+        Directions::Right,
+    ]
+    .iter()
+    .map(|direction| horizontal.lane_position(*direction))
+    .collect();
+    assert_eq!(lanes, vec![150., 50., -50., -150.]);
+    assert_eq!(
+        horizontal.translation(Directions::Up, TARGET_POSITION),
+        [TARGET_POSITION, 150., 1.].into()
+    );
+
+    // arrows rise from below the screen to the targets at the top, lanes left to right
+    let upscroll = Layout::upscroll();
+    assert_eq!(
+        upscroll.translation(Directions::Left, TARGET_POSITION),
+        [-150., TARGET_POSITION, 1.].into()
+    );
+    assert_eq!(
+        upscroll.translation(Directions::Right, SPAWN_POSITION).y,
+        SPAWN_POSITION
+    );
+
+    let downscroll = Layout::downscroll();
+    assert_eq!(
+        downscroll.translation(Directions::Up, TARGET_POSITION),
+        [50., -TARGET_POSITION, 1.].into()
+    );
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::{Input, KeyCode};
+use drum_city::{
+    map_maker::{merge_pass, recording_speed, OverdubMode},
+    types::{ArrowTimeToml, Directions, Speed},
+};
+
+fn arrow(click_time: f64, direction: Directions) -> ArrowTimeToml {
+    ArrowTimeToml {
+        click_time,
+        speed: Speed::Slow,
+        direction,
+        keysound: None,
+    }
+}
+
+fn arrows(merged: &[ArrowTimeToml]) -> Vec<(f64, Directions)> {
+    merged
+        .iter()
+        .map(|arrow| (arrow.click_time, arrow.direction))
+        .collect()
+}
+
+#[test]
+fn passes_only_change_their_armed_lanes() {
+    let existing = [arrow(1., Directions::Up), arrow(1., Directions::Down)];
+    // the Down press is in a lane that isn't armed, so it's dropped
+    let pass = [arrow(2., Directions::Up), arrow(3., Directions::Down)];
+
+    for mode in [OverdubMode::Merge, OverdubMode::Replace] {
+        let merged = merge_pass(&existing, &pass, &[Directions::Up], mode, 0.5);
+        assert_eq!(
+            arrows(&merged),
+            vec![
+                (1., Directions::Up),
+                (1., Directions::Down),
+                (2., Directions::Up)
+            ]
+        );
+    }
+}
+
+#[test]
+fn a_press_close_to_an_existing_arrow_replaces_it() {
+    let existing = [
+        arrow(1., Directions::Up),
+        arrow(2., Directions::Up),
+        arrow(1., Directions::Left),
+    ];
+    let pass = [arrow(1.05, Directions::Up), arrow(2.5, Directions::Up)];
+
+    let merged = merge_pass(
+        &existing,
+        &pass,
+        &[Directions::Up, Directions::Left],
+        OverdubMode::Merge,
+        3.,
+    );
+    assert_eq!(
+        arrows(&merged),
+        vec![
+            (1., Directions::Left),
+            (1.05, Directions::Up),
+            (2., Directions::Up),
+            (2.5, Directions::Up)
+        ]
+    );
+}
+
+#[test]
+fn replace_clears_armed_lanes_up_to_where_the_pass_reached() {
+    let existing = [
+        arrow(1., Directions::Up),
+        arrow(5., Directions::Up),
+        arrow(1., Directions::Right),
+    ];
+    let pass = [arrow(2., Directions::Up)];
+
+    let merged = merge_pass(
+        &existing,
+        &pass,
+        &[Directions::Up],
+        OverdubMode::Replace,
+        3.,
+    );
+    assert_eq!(
+        arrows(&merged),
+        vec![
+            (1., Directions::Right),
+            (2., Directions::Up),
+            (5., Directions::Up)
+        ]
+    );
+}
+
+#[test]
+fn saving_does_not_record_fast_arrows() {
+    let mut input = Input::<KeyCode>::default();
+    assert!(matches!(recording_speed(&input), Speed::Slow));
+    input.press(KeyCode::ShiftLeft);
+    assert!(matches!(recording_speed(&input), Speed::Medium));
+    input.press(KeyCode::ControlLeft);
+    assert!(matches!(recording_speed(&input), Speed::Fast));
+    // Ctrl+S saves, and arrows pressed meanwhile keep the speed they'd have without Ctrl
+    input.press(KeyCode::S);
+    assert!(matches!(recording_speed(&input), Speed::Medium));
+    input.release(KeyCode::ShiftLeft);
+    assert!(matches!(recording_speed(&input), Speed::Slow));
+}
@@ -0,0 +1,148 @@
+use drum_city::{
+    headless::Simulation,
+    midi::{MidiNoteOn, MidiSettings},
+    midi_import::{import_chart, ImportOptions},
+    settings::{Setting, Settings},
+    types::{read_config, Directions, LaneSet, SongConfigToml},
+};
+use midly::{
+    num::{u24, u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+const DRUM_CHART: &str = "akisey-dance-drums.toml";
+
+#[test]
+fn only_note_ons_are_read_and_mapped_to_lanes() {
+    assert_eq!(
+        MidiNoteOn::parse(&[0x99, 38, 100]),
+        Some(MidiNoteOn {
+            note: 38,
+            velocity: 100
+        })
+    );
+    // a note-on without velocity is a note-off
+    assert_eq!(MidiNoteOn::parse(&[0x99, 38, 0]), None);
+    assert_eq!(MidiNoteOn::parse(&[0x89, 38, 64]), None);
+    assert_eq!(MidiNoteOn::parse(&[0xF8]), None);
+
+    let settings = MidiSettings::default();
+    assert_eq!(settings.lane(36), Some(Directions::Kick));
+    assert_eq!(settings.lane(42), Some(Directions::HiHat));
+    assert_eq!(settings.lane(100), None);
+}
+
+#[test]
+fn midi_input_is_off_until_turned_on() {
+    let mut settings = Settings::default();
+    assert!(!settings.midi.enabled);
+    settings.change(Setting::Midi, 1);
+    assert!(settings.midi.enabled);
+    assert_eq!(settings.describe(Setting::Midi), "on");
+}
+
+#[test]
+fn drum_kit_notes_play_the_drum_lanes() {
+    let note = |direction| match direction {
+        Directions::Kick => 36,
+        Directions::Snare => 38,
+        Directions::HiHat => 42,
+        Directions::Tom => 45,
+        Directions::Crash => 49,
+        _ => unreachable!(),
+    };
+    let arrows = read_config(DRUM_CHART).arrows;
+    let mut simulation = Simulation::new(DRUM_CHART);
+    for arrow in arrows.iter() {
+        if simulation.song_time() < arrow.click_time {
+            simulation.run_until(arrow.click_time);
+        }
+        simulation.play_notes(&[(note(arrow.direction), 90)]);
+    }
+    simulation.run_for(5.);
+
+    let score = simulation.score();
+    assert_eq!(score.get_corrects(), arrows.len());
+    assert_eq!(score.get_fails(), 0);
+}
+
+fn event(delta: u32, kind: TrackEventKind) -> TrackEvent {
+    TrackEvent {
+        delta: u28::from(delta),
+        kind,
+    }
+}
+
+fn tempo(delta: u32, microseconds_per_beat: u32) -> TrackEvent<'static> {
+    event(
+        delta,
+        TrackEventKind::Meta(MetaMessage::Tempo(u24::from(microseconds_per_beat))),
+    )
+}
+
+fn note_on(delta: u32, channel: u8, note: u8) -> TrackEvent<'static> {
+    event(
+        delta,
+        TrackEventKind::Midi {
+            channel: u4::from(channel),
+            message: MidiMessage::NoteOn {
+                key: u7::from(note),
+                vel: u7::from(100),
+            },
+        },
+    )
+}
+
+#[test]
+fn drum_tracks_are_imported_with_their_tempo_changes() {
+    let end = event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack));
+    let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(480.into())));
+    // 120 BPM for two beats, then twice as fast
+    smf.tracks
+        .push(vec![tempo(0, 500_000), tempo(960, 250_000), end]);
+    smf.tracks.push(vec![
+        note_on(0, 9, 36),
+        // both kick notes at once are one arrow
+        note_on(0, 9, 35),
+        note_on(480, 9, 38),
+        // not on the drum channel
+        note_on(480, 0, 42),
+        // not a drum
+        note_on(0, 9, 60),
+        note_on(480, 9, 49),
+        end,
+    ]);
+    let mut midi = Vec::new();
+    smf.write_std(&mut midi).unwrap();
+
+    let chart = import_chart(&midi, "song.ogg", "Song", &ImportOptions::default()).unwrap();
+    let arrows: Vec<(f64, Directions)> = chart
+        .arrows
+        .iter()
+        .map(|arrow| (arrow.click_time, arrow.direction))
+        .collect();
+    assert_eq!(
+        arrows,
+        [
+            (0., Directions::Kick),
+            (0.5, Directions::Snare),
+            (1.25, Directions::Crash)
+        ]
+    );
+    assert_eq!(chart.bpm, Some(120.));
+    assert_eq!(chart.lanes, LaneSet::Drums);
+
+    // the chart file reads back as a drum chart
+    let text = toml::to_string(&chart).unwrap();
+    let read: SongConfigToml = toml::from_str(&text).unwrap();
+    assert_eq!(read.lanes, LaneSet::Drums);
+    assert_eq!(read.arrows.len(), 3);
+
+    let options = ImportOptions {
+        channel: 0,
+        ..Default::default()
+    };
+    let chart = import_chart(&midi, "song.ogg", "Song", &options).unwrap();
+    assert_eq!(chart.arrows.len(), 1);
+    assert_eq!(chart.arrows[0].direction, Directions::HiHat);
+}
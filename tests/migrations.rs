@@ -0,0 +1,102 @@
+use drum_city::{
+    migrations::{migrate, migrate_with, Migration, FORMAT_VERSION},
+    types::{parse_config, Directions},
+};
+
+#[test]
+fn charts_without_a_version_are_upgraded_on_load() {
+    let text = r#"
+name = "Old chart"
+filename = "song.ogg"
+
+[[arrows]]
+click_time = 1.0
+speed = "Slow"
+direction = "Up"
+"#;
+    let mut chart: toml::Value = toml::from_str(text).unwrap();
+    assert_eq!(migrate(&mut chart), Ok(0));
+    assert_eq!(
+        chart.get("format_version"),
+        Some(&toml::Value::Integer(FORMAT_VERSION as i64))
+    );
+
+    let config = parse_config(text).unwrap();
+    assert_eq!(config.format_version, FORMAT_VERSION);
+    assert_eq!(config.arrows.len(), 1);
+    assert_eq!(config.arrows[0].direction, Directions::Up);
+
+    // written back, the chart is at the latest version and doesn't need migrating again
+    let rewritten = toml::to_string(&config).unwrap();
+    assert!(rewritten.starts_with(&format!("format_version = {}", FORMAT_VERSION)));
+    let mut chart: toml::Value = toml::from_str(&rewritten).unwrap();
+    assert_eq!(migrate(&mut chart), Ok(FORMAT_VERSION));
+}
+
+#[test]
+fn charts_from_a_newer_version_are_refused() {
+    let text = format!(
+        "format_version = {}\nname = \"New chart\"\nfilename = \"song.ogg\"\narrows = []\n",
+        FORMAT_VERSION + 1
+    );
+    assert!(parse_config(&text).is_err());
+}
+
+/// A format change that renames a field, like a real one would
+fn rename_song_to_filename(chart: &mut toml::value::Table) {
+    if let Some(song) = chart.remove("song") {
+        chart.insert("filename".to_string(), song);
+    }
+}
+
+/// Another one, which gives every arrow a speed if it had none
+fn default_speeds(chart: &mut toml::value::Table) {
+    if let Some(toml::Value::Array(arrows)) = chart.get_mut("arrows") {
+        for arrow in arrows.iter_mut().filter_map(toml::Value::as_table_mut) {
+            arrow
+                .entry("speed")
+                .or_insert_with(|| toml::Value::String("Slow".to_string()));
+        }
+    }
+}
+
+#[test]
+fn migrations_run_in_order_from_the_charts_version() {
+    let chain: [Migration; 3] = [|_| {}, rename_song_to_filename, default_speeds];
+
+    // version 0 goes through every step
+    let mut chart: toml::Value = toml::from_str(
+        r#"
+name = "Old chart"
+song = "song.ogg"
+
+[[arrows]]
+click_time = 1.0
+direction = "Up"
+"#,
+    )
+    .unwrap();
+    assert_eq!(migrate_with(&mut chart, &chain), Ok(0));
+    assert_eq!(chart["format_version"].as_integer(), Some(3));
+    assert_eq!(chart["filename"].as_str(), Some("song.ogg"));
+    assert!(chart.get("song").is_none());
+    assert_eq!(chart["arrows"][0]["speed"].as_str(), Some("Slow"));
+
+    // version 2 only needs the last one: a `song` field is left alone
+    let mut chart: toml::Value = toml::from_str(
+        r#"
+format_version = 2
+name = "Newer chart"
+filename = "song.ogg"
+song = "kept"
+arrows = []
+"#,
+    )
+    .unwrap();
+    assert_eq!(migrate_with(&mut chart, &chain), Ok(2));
+    assert_eq!(chart["song"].as_str(), Some("kept"));
+    assert_eq!(chart["format_version"].as_integer(), Some(3));
+
+    let mut chart: toml::Value = toml::from_str("format_version = 4").unwrap();
+    assert!(migrate_with(&mut chart, &chain).is_err());
+}
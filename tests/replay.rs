@@ -0,0 +1,44 @@
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+use drum_city::{
+    modifiers::Modifiers,
+    replay::{stop_playback, Replay, ReplayPlayback},
+};
+
+#[test]
+fn watching_a_replay_leaves_the_players_modifiers_as_they_were() {
+    let mine = Modifiers {
+        hidden: true,
+        ..default()
+    };
+    let recorded = Modifiers {
+        rate: 1.5,
+        mirror: true,
+        ..default()
+    };
+    let replay = Replay {
+        chart: "map.toml".to_string(),
+        chart_hash: String::new(),
+        score: 0,
+        corrects: 0,
+        fails: 0,
+        modifiers: recorded,
+        events: Vec::new(),
+    };
+
+    let mut world = World::new();
+    let mut modifiers = mine;
+    world.insert_resource(ReplayPlayback::new(replay, &mut modifiers));
+    // the replay is watched with the modifiers it was recorded with
+    assert_eq!(modifiers, recorded);
+
+    world.insert_resource(modifiers);
+    world.run_system_once(stop_playback);
+    assert_eq!(*world.resource::<Modifiers>(), mine);
+    assert!(!world.contains_resource::<ReplayPlayback>());
+}
+
+#[test]
+fn missing_replays_are_an_error_not_a_crash() {
+    assert!(Replay::load("no such chart.toml").is_err());
+}
@@ -0,0 +1,124 @@
+use bevy::input::keyboard::KeyCode;
+use drum_city::{
+    audio::SoundChannel,
+    hit_sounds::{HitSoundMode, HitSoundSettings, LaneSounds},
+    layout::Layout,
+    score::Judgment,
+    scroll::ScrollSpeed,
+    settings::{Setting, Settings},
+    storage,
+    types::Directions,
+};
+
+#[test]
+fn settings_survive_a_round_trip_through_the_settings_file() {
+    let mut settings = Settings {
+        music_volume: 0.7,
+        audio_offset: -0.035,
+        scroll_speed: ScrollSpeed::CMod(1.5),
+        layout: Layout::downscroll(),
+        ..Default::default()
+    };
+    settings.key_bindings.set(Directions::Left, KeyCode::D);
+    settings.display.fullscreen = true;
+    settings.display.msaa = 8;
+
+    let text = toml::to_string(&settings).unwrap();
+    let loaded: Settings = toml::from_str(&text).unwrap();
+    assert_eq!(loaded, settings);
+
+    // settings missing from an older file keep their defaults
+    let loaded: Settings = toml::from_str("volume = 0.5").unwrap();
+    assert_eq!(loaded.volume(SoundChannel::Music), 0.5);
+    assert_eq!(loaded.key_bindings.key(Directions::Up), KeyCode::Up);
+}
+
+#[test]
+fn reserved_and_taken_keys_are_not_bound() {
+    let mut settings = Settings::default();
+    let bindings = &mut settings.key_bindings;
+
+    assert!(bindings.bind(Directions::Up, KeyCode::Escape).is_err());
+    assert!(bindings.bind(Directions::Kick, KeyCode::F5).is_err());
+    // practice and Map Maker controls
+    assert!(bindings.bind(Directions::Left, KeyCode::R).is_err());
+    assert!(bindings.bind(Directions::Tom, KeyCode::C).is_err());
+    assert!(bindings.bind(Directions::Right, KeyCode::T).is_err());
+    assert!(bindings.bind(Directions::Down, KeyCode::ShiftLeft).is_err());
+    // Down already plays the down lane
+    assert!(bindings.bind(Directions::Up, KeyCode::Down).is_err());
+    assert!(bindings.bind(Directions::Snare, KeyCode::D).is_err());
+    assert_eq!(bindings.key(Directions::Up), KeyCode::Up);
+    assert_eq!(bindings.key(Directions::Snare), KeyCode::F);
+
+    // drum keys can share keys with the arrows, and a lane can keep its own key
+    assert_eq!(bindings.bind(Directions::Up, KeyCode::D), Ok(()));
+    assert_eq!(bindings.bind(Directions::Snare, KeyCode::F), Ok(()));
+    assert_eq!(bindings.key(Directions::Up), KeyCode::D);
+}
+
+#[test]
+fn unreadable_saves_are_set_aside() {
+    let name = "target/unreadable_saves_are_set_aside.toml";
+    storage::write(name, "music_volume = \"loud\"");
+    assert!(toml::from_str::<Settings>(&storage::read(name).unwrap()).is_err());
+
+    let broken = storage::set_aside(name);
+    assert!(!storage::exists(name));
+    assert_eq!(
+        storage::read(&broken).as_deref(),
+        Some("music_volume = \"loud\"")
+    );
+}
+
+#[test]
+fn changes_stay_in_range() {
+    let mut settings = Settings::default();
+    let music = Setting::Volume(SoundChannel::Music);
+    settings.change(music, 3);
+    assert_eq!(settings.volume(SoundChannel::Music), 1.);
+    settings.change(music, -3);
+    assert_eq!(settings.describe(music), "70%");
+    // muting silences every channel but keeps their volumes
+    settings.muted = true;
+    assert_eq!(settings.volume(SoundChannel::Music), 0.);
+    assert_eq!(settings.volume(SoundChannel::Ui), 0.);
+    assert_eq!(settings.describe(music), "70%");
+
+    settings.change(Setting::InputOffset, -1000);
+    assert_eq!(settings.input_offset, -0.5);
+    settings.change(Setting::AudioOffset, 3);
+    assert_eq!(settings.describe(Setting::AudioOffset), "+15 ms");
+
+    settings.change(Setting::Msaa, 1);
+    assert_eq!(settings.describe(Setting::Msaa), "8x");
+    settings.change(Setting::Msaa, 1);
+    assert_eq!(settings.describe(Setting::Msaa), "off");
+}
+
+#[test]
+fn hit_sounds_follow_the_judgment_unless_the_lane_has_its_own() {
+    let mut hit_sounds = HitSoundSettings {
+        lanes: LaneSounds {
+            left: Some("kick.wav".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        hit_sounds.sound(Directions::Up, Judgment::Great),
+        Some("hit_great.wav")
+    );
+    assert_eq!(
+        hit_sounds.sound(Directions::Left, Judgment::Perfect),
+        Some("kick.wav")
+    );
+    assert_eq!(hit_sounds.sound(Directions::Left, Judgment::Miss), None);
+
+    // keysounds can still play without the hit sounds
+    hit_sounds.mode = HitSoundMode::KeysoundsOnly;
+    assert_eq!(hit_sounds.sound(Directions::Up, Judgment::Perfect), None);
+    assert!(hit_sounds.plays_keysounds());
+    hit_sounds.mode = HitSoundMode::Off;
+    assert!(!hit_sounds.plays_keysounds());
+}
@@ -0,0 +1,17 @@
+use std::fs::read_dir;
+
+use drum_city::chart::SongIndex;
+
+#[test]
+fn the_song_index_lists_every_chart_in_assets() {
+    let mut charts: Vec<String> = read_dir("assets/songs")
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".toml"))
+        .collect();
+    charts.sort();
+
+    let mut listed = SongIndex::read().charts;
+    listed.sort();
+    assert_eq!(listed, charts, "run `cargo run -- index` to update it");
+}
@@ -0,0 +1,55 @@
+use drum_city::stretch::TimeStretch;
+use rodio::buffer::SamplesBuffer;
+
+const SAMPLE_RATE: u32 = 44100;
+
+/// Two seconds of a 440 Hz tone, in stereo
+fn tone() -> SamplesBuffer<i16> {
+    let samples: Vec<i16> = (0..SAMPLE_RATE as usize * 2)
+        .flat_map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let sample = ((t * 440. * std::f32::consts::TAU).sin() * 10000.) as i16;
+            [sample, sample]
+        })
+        .collect();
+    SamplesBuffer::new(2, SAMPLE_RATE, samples)
+}
+
+/// Pitch of the left channel, from its rising zero crossings away from the ends
+fn frequency(samples: &[i16]) -> f32 {
+    let left: Vec<i16> = samples.iter().step_by(2).copied().collect();
+    let middle = &left[left.len() / 4..left.len() * 3 / 4];
+    let crossings = middle
+        .windows(2)
+        .filter(|pair| pair[0] < 0 && pair[1] >= 0)
+        .count();
+    crossings as f32 * SAMPLE_RATE as f32 / middle.len() as f32
+}
+
+#[test]
+fn rates_change_the_length_but_keep_the_pitch() {
+    for rate in [0.5, 0.75, 1.5, 2.] {
+        let stretched: Vec<i16> = TimeStretch::new(tone(), rate).collect();
+        let seconds = stretched.len() as f32 / 2. / SAMPLE_RATE as f32;
+        assert!(
+            (seconds - 2. / rate).abs() < 0.05,
+            "{}x lasts {}s",
+            rate,
+            seconds
+        );
+        let pitch = frequency(&stretched);
+        assert!(
+            (pitch - 440.).abs() < 10.,
+            "{}x plays at {} Hz",
+            rate,
+            pitch
+        );
+    }
+}
+
+#[test]
+fn normal_rate_leaves_the_audio_as_it_is() {
+    let original: Vec<i16> = tone().collect();
+    let played: Vec<i16> = TimeStretch::new(tone(), 1.).collect();
+    assert_eq!(played, original);
+}
@@ -0,0 +1,38 @@
+use drum_city::analysis::tempo_from_taps;
+
+#[test]
+fn evenly_spaced_taps_give_their_tempo() {
+    // 120 BPM from 1.25 seconds in
+    let taps: Vec<f64> = (0..8).map(|beat| 1.25 + beat as f64 * 0.5).collect();
+    let (bpm, offset) = tempo_from_taps(&taps).unwrap();
+    assert!((bpm - 120.).abs() < 1e-9);
+    // the first beat of the song, a whole number of beats before the first tap
+    assert!((offset - 0.25).abs() < 1e-9);
+}
+
+#[test]
+fn jittered_taps_average_out() {
+    let jitter = [0.02, -0.015, 0.01, -0.02, 0.005, 0.015, -0.01, -0.005];
+    let taps: Vec<f64> = jitter
+        .iter()
+        .enumerate()
+        .map(|(beat, jitter)| 2. + beat as f64 * 0.4 + jitter)
+        .collect();
+    let (bpm, offset) = tempo_from_taps(&taps).unwrap();
+    assert!((bpm - 150.).abs() < 2., "{} BPM", bpm);
+    // the taps start 5 beats in, so the first beat is at the start of the song, or a jitter
+    // before the next beat
+    assert!(
+        (offset - 0.4).abs() < 0.02 || offset < 0.02,
+        "offset {}",
+        offset
+    );
+}
+
+#[test]
+fn too_few_taps_have_no_tempo() {
+    assert_eq!(tempo_from_taps(&[]), None);
+    assert_eq!(tempo_from_taps(&[1.]), None);
+    // taps that don't go forward in time
+    assert_eq!(tempo_from_taps(&[2., 1.]), None);
+}